@@ -1,4 +1,4 @@
-use std::{fmt, string::ToString};
+use std::{fmt, path::PathBuf, string::ToString, sync::Arc};
 
 use serde::{
     de::{self, Deserializer, Visitor},
@@ -6,6 +6,21 @@ use serde::{
 };
 use strum::Display;
 
+use crate::{
+    components::prompt::{Choice, PromptPurpose},
+    mode::Mode,
+    services::{
+        fileops::FileOp,
+        grep::GrepMatch,
+        jobs::JobProgress,
+        journal::{JournalEntry, Replay},
+        listing::DirEntry,
+        opener::Launch,
+        preview::{HexPage, ImagePreview, Preview},
+        trash::TrashedItem,
+    },
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Display, Deserialize)]
 pub enum Action {
     Tick,
@@ -13,8 +28,146 @@ pub enum Action {
     Resize(u16, u16),
     Suspend,
     Resume,
+    Subshell,
     Quit,
     Refresh,
     Error(String),
     Help,
+    GoToParent,
+    Open,
+    HistoryBack,
+    HistoryForward,
+    SelectNext,
+    SelectPrevious,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    SelectFirst,
+    SelectLast,
+    CycleSort,
+    ReverseSort,
+    ToggleDirsFirst,
+    ToggleSortCase,
+    SwitchMode(Mode),
+    EnterFilter,
+    AcceptFilter,
+    ClearFilter,
+    CycleFilterKind,
+    ToggleHidden,
+    DirectoryChanged(PathBuf),
+    Reveal(PathBuf),
+    OpenFind,
+    CloseFind,
+    FindResults(usize, Vec<PathBuf>),
+    FindDone(usize),
+    OpenGrep,
+    CloseGrep,
+    CancelGrep,
+    ToggleGrepRegex,
+    GrepResults(usize, Vec<GrepMatch>),
+    /// Whether the search stopped at [`MAX_MATCHES`](crate::services::grep::MAX_MATCHES).
+    GrepDone(usize, bool),
+    Edit(PathBuf, Option<usize>),
+    Edited(PathBuf),
+    /// The editor could not be run on the file, or exited with a failure.
+    EditFailed(PathBuf),
+    OpenWith,
+    Launch(Vec<Launch>),
+    RunCommand(String),
+    ShowOutput(String, String),
+    CloseOutput,
+    EnterCommandLine,
+    OpenCommandLine(Vec<String>),
+    SubmitCommandLine,
+    CancelCommandLine,
+    CompleteCommandLine,
+    CancelCommand,
+    OutputStarted(usize, String),
+    OutputLines(usize, Vec<String>),
+    OutputDone(usize, Result<(), String>),
+    Copy,
+    Move,
+    Rename,
+    Delete,
+    CreateDir,
+    CreateFile,
+    FileOp(FileOp),
+    Prompt(PromptPurpose, String),
+    PromptSubmitted(PromptPurpose, String),
+    Confirm(String, Box<Action>),
+    Choose(String, Vec<Choice>),
+    SubmitPrompt,
+    CancelPrompt,
+    ToggleMark,
+    MarkAll,
+    InvertMarks,
+    ClearMarks,
+    EnterVisual,
+    AcceptVisual,
+    CancelVisual,
+    Yank,
+    Cut,
+    Paste,
+    ClearRegister,
+    Trash,
+    OpenTrash,
+    CloseTrash,
+    RestoreTrashed,
+    PurgeTrashed,
+    EmptyTrash,
+    TrashListed(Vec<TrashedItem>),
+    Undo,
+    Redo,
+    BulkRename,
+    EditNames(Vec<PathBuf>),
+    ApplyRenames,
+    CancelRenames,
+    PatternRename,
+    OpenPatternRename(Vec<PathBuf>),
+    NextField,
+    PreviousField,
+    CycleCase,
+    Recorded(JournalEntry),
+    JournalApplied(Replay, JournalEntry, Result<(), String>),
+    OpenJobs,
+    CloseJobs,
+    ToggleJobPause,
+    CancelJob,
+    RetryFailedFile,
+    SkipFailedFile,
+    JobStarted(usize, String),
+    JobProgress(usize, JobProgress),
+    JobFailed(usize, PathBuf, String),
+    JobDone(usize),
+    SelectionChanged(Option<PathBuf>),
+    PreviewLoaded(Preview),
+    TogglePreview,
+    ScrollPreviewDown,
+    ScrollPreviewUp,
+    PreviewPageDown,
+    PreviewPageUp,
+    JumpToOffset,
+    HexLoaded(HexPage),
+    ImageLoaded(ImagePreview),
+    /// Shared, as every component gets a copy of every action.
+    ListingBatch(usize, Arc<Vec<DirEntry>>),
+    ListingDone(usize),
+}
+
+impl Action {
+    /// Whether the action carries loaded data, like entries or previews, which is too much to log.
+    pub fn is_bulky(&self) -> bool {
+        matches!(
+            self,
+            Action::FindResults(..)
+                | Action::GrepResults(..)
+                | Action::OutputLines(..)
+                | Action::TrashListed(..)
+                | Action::PreviewLoaded(..)
+                | Action::HexLoaded(..)
+                | Action::ImageLoaded(..)
+                | Action::ListingBatch(..)
+        )
+    }
 }
@@ -1,3 +1,10 @@
+use std::{
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
 use color_eyre::eyre::Result;
 use crossterm::event::KeyEvent;
 use ratatui::prelude::Rect;
@@ -6,10 +13,15 @@ use tokio::sync::mpsc;
 
 use crate::{
     action::Action,
-    components::{fps::FpsCounter, home::Home, Component},
+    components::{
+        bulk_rename::BulkRename, command_line::CommandLine, find::Find, fps::FpsCounter, grep::Grep, home::Home,
+        jobs::Jobs, output::Output, parent::Parent, pattern_rename::PatternRename, preview::Preview, prompt::Prompt,
+        trash::Trash, Component,
+    },
     config::Config,
     mode::Mode,
-    tui,
+    services::opener::{Launch, LaunchMode},
+    tui, utils,
 };
 
 pub struct App {
@@ -21,23 +33,50 @@ pub struct App {
     pub should_suspend: bool,
     pub mode: Mode,
     pub last_tick_key_events: Vec<KeyEvent>,
+    /// Working directory of the listing, which subshells start in.
+    pub cwd: Option<PathBuf>,
 }
 
 impl App {
-    pub fn new(tick_rate: f64, frame_rate: f64) -> Result<Self> {
-        let home = Home::new();
+    pub fn new(tick_rate: f64, frame_rate: f64, path: Option<PathBuf>) -> Result<Self> {
+        let home = Home::new(path)?;
+        let parent = Parent::new();
+        let preview = Preview::new();
+        let find = Find::new();
+        let grep = Grep::new();
+        let jobs = Jobs::new();
+        let trash = Trash::new();
+        let output = Output::new();
+        let command_line = CommandLine::new();
+        let bulk_rename = BulkRename::new();
+        let pattern_rename = PatternRename::new();
+        let prompt = Prompt::new();
         let fps = FpsCounter::default();
         let config = Config::new()?;
         let mode = Mode::Home;
         Ok(Self {
             tick_rate,
             frame_rate,
-            components: vec![Box::new(home) /* , Box::new(fps) */],
+            components: vec![
+                Box::new(home),
+                Box::new(parent),
+                Box::new(preview),
+                Box::new(find),
+                Box::new(grep),
+                Box::new(jobs),
+                Box::new(trash),
+                Box::new(output),
+                Box::new(command_line),
+                Box::new(bulk_rename),
+                Box::new(pattern_rename),
+                Box::new(prompt), // , Box::new(fps)
+            ],
             should_quit: false,
             should_suspend: false,
             config,
             mode,
             last_tick_key_events: Vec::new(),
+            cwd: None,
         })
     }
 
@@ -95,7 +134,9 @@ impl App {
             }
 
             while let Ok(action) = action_rx.try_recv() {
-                if action != Action::Tick && action != Action::Render {
+                if action.is_bulky() {
+                    log::debug!("{action}");
+                } else if action != Action::Tick && action != Action::Render {
                     log::debug!("{action:?}");
                 }
                 match action {
@@ -105,26 +146,48 @@ impl App {
                     Action::Quit => self.should_quit = true,
                     Action::Suspend => self.should_suspend = true,
                     Action::Resume => self.should_suspend = false,
+                    Action::DirectoryChanged(ref path) => self.cwd = Some(path.clone()),
+                    Action::Subshell => {
+                        let mut command = shell_command(self.cwd.as_deref());
+                        match self.run_external(&mut tui, &mut command) {
+                            // The shell exits with the status of the last command run in it, which is no failure.
+                            // What was run may have changed the files in the listing.
+                            Ok(_) => action_tx.send(Action::Refresh)?,
+                            Err(e) => action_tx.send(Action::Error(format!("Failed to start a shell: {e}")))?,
+                        }
+                    },
+                    Action::Edit(ref path, line) => {
+                        let mut command = editor_command(path, line);
+                        let error = match self.run_external(&mut tui, &mut command) {
+                            Ok(status) if !status.success() => Some(format!("Editor exited with {status}")),
+                            Ok(_) => None,
+                            Err(e) => Some(format!("Failed to run the editor: {e}")),
+                        };
+                        match error {
+                            Some(error) => {
+                                action_tx.send(Action::Error(error))?;
+                                action_tx.send(Action::EditFailed(path.clone()))?;
+                            },
+                            None => action_tx.send(Action::Edited(path.clone()))?,
+                        }
+                    },
+                    Action::Launch(ref launches) => {
+                        for launch in launches {
+                            if let Some(action) = self.launch(&mut tui, launch) {
+                                action_tx.send(action)?;
+                            }
+                        }
+                    },
+                    Action::SwitchMode(mode) => {
+                        self.mode = mode;
+                        self.last_tick_key_events.drain(..);
+                    },
                     Action::Resize(w, h) => {
                         tui.resize(Rect::new(0, 0, w, h))?;
-                        tui.draw(|f| {
-                            for component in self.components.iter_mut() {
-                                let r = component.draw(f, f.size());
-                                if let Err(e) = r {
-                                    action_tx.send(Action::Error(format!("Failed to draw: {:?}", e))).unwrap();
-                                }
-                            }
-                        })?;
+                        self.render(&mut tui, &action_tx)?;
                     },
                     Action::Render => {
-                        tui.draw(|f| {
-                            for component in self.components.iter_mut() {
-                                let r = component.draw(f, f.size());
-                                if let Err(e) = r {
-                                    action_tx.send(Action::Error(format!("Failed to draw: {:?}", e))).unwrap();
-                                }
-                            }
-                        })?;
+                        self.render(&mut tui, &action_tx)?;
                     },
                     _ => {},
                 }
@@ -148,4 +211,80 @@ impl App {
         tui.exit()?;
         Ok(())
     }
+
+    /// Draws a frame of every component, then lets them write over it.
+    fn render(&mut self, tui: &mut tui::Tui, action_tx: &mpsc::UnboundedSender<Action>) -> Result<()> {
+        tui.draw(|f| {
+            for component in self.components.iter_mut() {
+                let r = component.draw(f, f.size());
+                if let Err(e) = r {
+                    action_tx.send(Action::Error(format!("Failed to draw: {:?}", e))).unwrap();
+                }
+            }
+        })?;
+        for component in self.components.iter_mut() {
+            if let Err(e) = component.draw_over(tui.backend_mut()) {
+                action_tx.send(Action::Error(format!("Failed to draw: {:?}", e)))?;
+            }
+        }
+        tui.backend_mut().flush()?;
+        Ok(())
+    }
+
+    /// Hands the terminal over to `command` until it exits, then takes it back with a fresh `Tui`.
+    fn run_external(&mut self, tui: &mut tui::Tui, command: &mut Command) -> Result<ExitStatus> {
+        tui.exit()?;
+        let status = command.status();
+        *tui = tui::Tui::new()?.tick_rate(self.tick_rate).frame_rate(self.frame_rate);
+        // tui.mouse(true);
+        tui.enter()?;
+        Ok(status?)
+    }
+
+    /// Runs `launch` the way its mode says, and returns what to do once it is over.
+    fn launch(&mut self, tui: &mut tui::Tui, launch: &Launch) -> Option<Action> {
+        let failed = |e: &dyn Display| Action::Error(format!("Failed to run {}: {e}", launch.program));
+        match launch.mode {
+            LaunchMode::Detached => launch.spawn_detached().err().map(|e| failed(&e)),
+            LaunchMode::Block => Some(launch.run_blocking().map_or_else(Action::Error, |()| Action::Refresh)),
+            LaunchMode::Suspend => {
+                Some(match self.run_external(tui, &mut launch.command()) {
+                    Ok(status) if !status.success() => {
+                        Action::Error(format!("{} exited with {status}", launch.program))
+                    },
+                    // It may have changed the files in the listing.
+                    Ok(_) => Action::Refresh,
+                    Err(e) => failed(&e),
+                })
+            },
+        }
+    }
+}
+
+/// Builds the command that starts the user's shell in `dir`, with `FE_RS_LEVEL` in its environment telling how many
+/// subshells deep it is, e.g. for the prompt to show it.
+fn shell_command(dir: Option<&Path>) -> Command {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_owned());
+    let variable = format!("{}_LEVEL", utils::PROJECT_NAME.as_str());
+    let level = std::env::var(&variable).ok().and_then(|level| level.parse::<u32>().ok()).unwrap_or_default();
+    let mut command = Command::new(shell);
+    command.env(variable, (level + 1).to_string());
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    command
+}
+
+/// Builds the command that opens `path` in the user's editor, at `line` if given.
+fn editor_command(path: &Path, line: Option<usize>) -> Command {
+    let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".to_owned());
+    // The editor may come with arguments of its own, e.g. `code --wait`.
+    let mut words = editor.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or("vi"));
+    command.args(words);
+    if let Some(line) = line {
+        command.arg(format!("+{line}"));
+    }
+    command.arg(path);
+    command
 }
@@ -24,4 +24,7 @@ pub struct Cli {
         default_value_t = 60.
     )]
     pub frame_rate: f64,
+
+    #[arg(value_name = "PATH", help = "Directory to start in, defaults to the current working directory")]
+    pub path: Option<PathBuf>,
 }
@@ -0,0 +1,187 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::services::listing::{DirEntry, EntryKind};
+
+/// A column of the detail view in `Home`. Which columns are shown, and in what order, comes from the `columns` list
+/// in the config.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Column {
+    Name,
+    Size,
+    Modified,
+    Permissions,
+    Owner,
+    Group,
+    Type,
+    LinkTarget,
+}
+
+impl Column {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Size => "Size",
+            Column::Modified => "Modified",
+            Column::Permissions => "Permissions",
+            Column::Owner => "Owner",
+            Column::Group => "Group",
+            Column::Type => "Type",
+            Column::LinkTarget => "Target",
+        }
+    }
+
+    /// Fixed width of the column, or `None` if it shares whatever space is left with the other flexible columns.
+    pub fn width(&self) -> Option<u16> {
+        match self {
+            Column::Name | Column::LinkTarget => None,
+            Column::Size => Some(10),
+            Column::Modified => Some(16),
+            Column::Permissions => Some(11),
+            Column::Owner | Column::Group => Some(10),
+            Column::Type => Some(7),
+        }
+    }
+
+    /// Text of the column for `entry`. The name column is left to the caller since it is decorated with an icon.
+    pub fn cell(&self, entry: &DirEntry) -> String {
+        match self {
+            Column::Name => entry.name.clone(),
+            Column::Size => entry.size.map(human_size).unwrap_or_default(),
+            Column::Modified => entry.modified.map(format_time).unwrap_or_default(),
+            Column::Permissions => entry.mode.map(|mode| permissions(entry.kind, mode)).unwrap_or_default(),
+            Column::Owner => entry.owner.clone().unwrap_or_default(),
+            Column::Group => entry.group.clone().unwrap_or_default(),
+            Column::Type => kind_name(entry.kind).to_owned(),
+            Column::LinkTarget => {
+                entry.link_target.as_ref().map(|target| target.display().to_string()).unwrap_or_default()
+            },
+        }
+    }
+}
+
+/// Space left between two columns of the table.
+pub const COLUMN_SPACING: u16 = 1;
+
+/// Narrowest a flexible column is allowed to get before columns are dropped to make room.
+const MIN_FLEXIBLE_WIDTH: u16 = 12;
+
+/// Drops columns from the right, never the name, until the rest fit into `width`.
+pub fn fit(columns: &[Column], width: u16) -> Vec<Column> {
+    let mut columns = columns.to_vec();
+    if !columns.contains(&Column::Name) {
+        columns.insert(0, Column::Name);
+    }
+    loop {
+        let needed = columns.iter().map(|column| column.width().unwrap_or(MIN_FLEXIBLE_WIDTH)).sum::<u16>()
+            + COLUMN_SPACING * (columns.len() as u16 - 1);
+        if needed <= width || columns.len() == 1 {
+            return columns;
+        }
+        let last_droppable = columns.iter().rposition(|column| *column != Column::Name).unwrap();
+        columns.remove(last_droppable);
+    }
+}
+
+/// Formats a byte count with binary units, e.g. `1.5 KiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Formats permission bits the way `ls -l` does, e.g. `drwxr-xr-x`.
+pub fn permissions(kind: EntryKind, mode: u32) -> String {
+    let mut s = String::with_capacity(10);
+    s.push(match kind {
+        EntryKind::Dir => 'd',
+        EntryKind::Symlink => 'l',
+        EntryKind::BlockDevice => 'b',
+        EntryKind::CharDevice => 'c',
+        EntryKind::Fifo => 'p',
+        EntryKind::Socket => 's',
+        EntryKind::File | EntryKind::Unknown => '-',
+    });
+    // (read bit, write bit, execute bit, special bit, special char when executable, special char otherwise)
+    let triads = [
+        (0o400, 0o200, 0o100, 0o4000, 's', 'S'),
+        (0o040, 0o020, 0o010, 0o2000, 's', 'S'),
+        (0o004, 0o002, 0o001, 0o1000, 't', 'T'),
+    ];
+    for (read, write, execute, special, special_exec, special_no_exec) in triads {
+        s.push(if mode & read != 0 { 'r' } else { '-' });
+        s.push(if mode & write != 0 { 'w' } else { '-' });
+        s.push(match (mode & execute != 0, mode & special != 0) {
+            (true, true) => special_exec,
+            (false, true) => special_no_exec,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+pub fn kind_name(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::File => "file",
+        EntryKind::Dir => "dir",
+        EntryKind::Symlink => "symlink",
+        EntryKind::BlockDevice => "block",
+        EntryKind::CharDevice => "char",
+        EntryKind::Fifo => "fifo",
+        EntryKind::Socket => "socket",
+        EntryKind::Unknown => "?",
+    }
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_fit() {
+        let columns = [Column::Name, Column::Size, Column::Modified, Column::Permissions];
+        assert_eq!(fit(&columns, 200), columns.to_vec());
+        assert_eq!(fit(&columns, 40), vec![Column::Name, Column::Size, Column::Modified]);
+        assert_eq!(fit(&columns, 5), vec![Column::Name]);
+        assert_eq!(fit(&[Column::Size], 200), vec![Column::Name, Column::Size]);
+    }
+
+    #[test]
+    fn test_permissions() {
+        assert_eq!(permissions(EntryKind::Dir, 0o755), "drwxr-xr-x");
+        assert_eq!(permissions(EntryKind::File, 0o640), "-rw-r-----");
+        assert_eq!(permissions(EntryKind::Symlink, 0o777), "lrwxrwxrwx");
+    }
+
+    #[test]
+    fn test_permissions_special_bits() {
+        assert_eq!(permissions(EntryKind::File, 0o4755), "-rwsr-xr-x");
+        assert_eq!(permissions(EntryKind::File, 0o2644), "-rw-r-Sr--");
+        assert_eq!(permissions(EntryKind::Dir, 0o1777), "drwxrwxrwt");
+    }
+}
@@ -1,6 +1,9 @@
+use std::io::Write;
+
 use color_eyre::eyre::Result;
 use crossterm::event::{KeyEvent, MouseEvent};
-use ratatui::layout::Rect;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
@@ -9,8 +12,19 @@ use crate::{
     tui::{Event, Frame},
 };
 
+pub mod bulk_rename;
+pub mod command_line;
+pub mod find;
 pub mod fps;
+pub mod grep;
 pub mod home;
+pub mod jobs;
+pub mod output;
+pub mod parent;
+pub mod pattern_rename;
+pub mod preview;
+pub mod prompt;
+pub mod trash;
 
 /// `Component` is a trait that represents a visual and interactive element of the user interface.
 /// Implementors of this trait can be registered with the main application loop and will be able to receive events,
@@ -121,4 +135,80 @@ pub trait Component {
     ///
     /// * `Result<()>` - An Ok result or an error.
     fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()>;
+    /// Write what can't go through a frame, like image graphics, straight to the terminal once the frame is drawn.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The terminal backend.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - An Ok result or an error.
+    #[allow(unused_variables)]
+    fn draw_over(&mut self, out: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// How the main view is laid out, picked by `layout` in the config.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LayoutMode {
+    /// The listing, with the preview to its right.
+    #[default]
+    Split,
+    /// Miller columns, as in ranger: the parent directory, the listing and the preview side by side.
+    Miller,
+}
+
+/// Areas of the main view: the title and status lines of the listing above and below it, the preview to its right
+/// when it is shown, and the parent directory to its left in the Miller layout.
+pub struct MainLayout {
+    pub title: Rect,
+    pub parent: Option<Rect>,
+    pub listing: Rect,
+    pub preview: Option<Rect>,
+    pub status: Rect,
+}
+
+pub fn main_layout(area: Rect, mode: LayoutMode, show_preview: bool) -> MainLayout {
+    let [title, middle, status] = *Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)])
+        .split(area)
+    else {
+        unreachable!()
+    };
+    let constraints = match (mode, show_preview) {
+        (LayoutMode::Split, false) => vec![Constraint::Percentage(100)],
+        (LayoutMode::Split, true) => vec![Constraint::Percentage(50), Constraint::Percentage(50)],
+        (LayoutMode::Miller, false) => vec![Constraint::Percentage(25), Constraint::Percentage(75)],
+        (LayoutMode::Miller, true) => {
+            vec![Constraint::Percentage(20), Constraint::Percentage(40), Constraint::Percentage(40)]
+        },
+    };
+    let mut panes = Layout::default().direction(Direction::Horizontal).constraints(constraints).split(middle).to_vec();
+    let preview = if show_preview { panes.pop() } else { None };
+    let listing = panes.pop().unwrap();
+    MainLayout { title, parent: panes.pop(), listing, preview, status }
+}
+
+/// Returns a rectangle of `percent_x` by `percent_y` of `area`, centered in it. Used for popups drawn over the
+/// listing.
+pub fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
 }
@@ -0,0 +1,256 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{centered_rect, Component, Frame};
+use crate::{
+    action::Action,
+    mode::Mode,
+    services::fileops::{display_name, rename_conflicts, FileOp},
+    utils,
+};
+
+/// Renames several entries at once by editing their names in the user's editor, one per line, then showing what
+/// would be renamed before doing it.
+#[derive(Default)]
+pub struct BulkRename {
+    command_tx: Option<UnboundedSender<Action>>,
+    mode: Mode,
+    /// The file handed to the editor and the entries whose names it lists, in order.
+    editing: Option<(PathBuf, Vec<PathBuf>)>,
+    renames: Vec<(PathBuf, PathBuf)>,
+    conflicts: Vec<Option<&'static str>>,
+    curr_index: usize,
+    offset: usize,
+    viewport_height: usize,
+}
+
+impl BulkRename {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the names to a file and asks for it to be opened in the editor.
+    fn edit_names(&mut self, paths: Vec<PathBuf>) -> Option<Action> {
+        let names =
+            paths.iter().map(|path| path.file_name().and_then(|name| name.to_str())).collect::<Option<Vec<_>>>();
+        let Some(names) = names.filter(|names| names.iter().all(|name| !name.contains('\n'))) else {
+            return Some(Action::Error("Can't edit names that are not valid UTF-8 or span lines".to_owned()));
+        };
+        let contents = names.iter().map(|name| format!("{name}\n")).collect::<String>();
+        let file = utils::create_temp_file("rename", ".txt").and_then(|(file, mut handle)| {
+            handle.write_all(contents.as_bytes()).inspect_err(|_| {
+                let _ = fs::remove_file(&file);
+            })?;
+            Ok(file)
+        });
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => return Some(Action::Error(format!("Failed to write the names to edit: {e}"))),
+        };
+        self.editing = Some((file.clone(), paths));
+        Some(Action::Edit(file, None))
+    }
+
+    /// Forgets about the names being edited if `file` is the one they were written to, removing it.
+    fn discard_edit(&mut self, file: &Path) {
+        if self.editing.as_ref().is_some_and(|(editing, _)| editing == file) {
+            self.editing = None;
+            let _ = fs::remove_file(file);
+        }
+    }
+
+    /// Reads back the edited names and shows the renames they make.
+    fn on_edited(&mut self) -> Option<Action> {
+        let (file, paths) = self.editing.take()?;
+        let contents = fs::read_to_string(&file);
+        let _ = fs::remove_file(&file);
+        let renames = contents.map_err(|e| e.to_string()).and_then(|contents| diff_names(&paths, &contents));
+        match renames {
+            Ok(renames) if renames.is_empty() => None,
+            Ok(renames) => {
+                self.conflicts = rename_conflicts(&renames);
+                self.renames = renames;
+                self.curr_index = 0;
+                self.offset = 0;
+                Some(Action::SwitchMode(Mode::BulkRename))
+            },
+            Err(e) => Some(Action::Error(format!("Nothing was renamed: {e}"))),
+        }
+    }
+
+    fn apply(&mut self) -> Option<Action> {
+        if self.conflicts.iter().any(Option::is_some) {
+            return None;
+        }
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.send(Action::SwitchMode(Mode::Home));
+        }
+        Some(Action::FileOp(FileOp::RenameAll { renames: std::mem::take(&mut self.renames) }))
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        self.curr_index = self.curr_index.saturating_add_signed(delta).min(self.renames.len().saturating_sub(1));
+    }
+}
+
+/// Pairs each path with the name on the same line of `contents`, leaving out the names that didn't change.
+fn diff_names(paths: &[PathBuf], contents: &str) -> std::result::Result<Vec<(PathBuf, PathBuf)>, String> {
+    let names = contents.lines().map(|line| line.strip_suffix('\r').unwrap_or(line)).collect::<Vec<_>>();
+    if names.len() != paths.len() {
+        return Err(format!("expected {} names but got {}", paths.len(), names.len()));
+    }
+    let mut renames = vec![];
+    for (path, name) in paths.iter().zip(names) {
+        if name.is_empty() || name == "." || name == ".." || name.contains(std::path::is_separator) {
+            return Err(format!("invalid name: {name:?}"));
+        }
+        if path.file_name().is_some_and(|old| old != name) {
+            renames.push((path.clone(), path.with_file_name(name)));
+        }
+    }
+    Ok(renames)
+}
+
+/// Row of a table of renames, with the reason the rename can't be made in red.
+pub(super) fn rename_row(old_name: String, new_name: String, conflict: Option<&str>, selected: bool) -> Row<'static> {
+    let new_name = match conflict {
+        Some(reason) => Line::from(format!("{new_name} ({reason})")).red(),
+        None => Line::from(new_name),
+    };
+    let row = Row::new([Cell::from(old_name), Cell::from("→"), Cell::from(new_name)]);
+    if selected {
+        row.style(Style::new().bg(Color::Magenta))
+    } else {
+        row
+    }
+}
+
+/// Widths of the columns of the rows made by [`rename_row`].
+pub(super) const RENAME_TABLE_WIDTHS: [Constraint; 3] =
+    [Constraint::Fill(1), Constraint::Length(1), Constraint::Fill(1)];
+
+/// One line summing up the renames, or what stops them.
+pub(super) fn rename_status(count: usize, conflicts: &[Option<&'static str>]) -> Line<'static> {
+    match conflicts.iter().filter(|conflict| conflict.is_some()).count() {
+        0 => Line::from(format!("{count} renames · [Enter] rename [Esc] cancel").dim()),
+        n => Line::from(format!("{n} conflicting names, nothing can be renamed").red()),
+    }
+}
+
+impl Component for BulkRename {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::EditNames(paths) => return Ok(self.edit_names(paths)),
+            Action::Edited(ref file) if self.editing.as_ref().is_some_and(|(editing, _)| editing == file) => {
+                return Ok(self.on_edited());
+            },
+            Action::EditFailed(ref file) => self.discard_edit(file),
+            _ if self.mode != Mode::BulkRename => {},
+            Action::ApplyRenames => return Ok(self.apply()),
+            Action::CancelRenames => {
+                self.renames.clear();
+                return Ok(Some(Action::SwitchMode(Mode::Home)));
+            },
+            Action::SelectNext => self.move_cursor(1),
+            Action::SelectPrevious => self.move_cursor(-1),
+            Action::PageDown => self.move_cursor(self.viewport_height as isize),
+            Action::PageUp => self.move_cursor(-(self.viewport_height as isize)),
+            Action::SelectFirst => self.curr_index = 0,
+            Action::SelectLast => self.move_cursor(isize::MAX),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::BulkRename {
+            return Ok(());
+        }
+
+        let area = centered_rect(80, 80, area);
+        f.render_widget(Clear, area);
+        let title = match self.renames.first().and_then(|(from, _)| from.parent()) {
+            Some(parent) => format!("Rename in {}", parent.display()),
+            None => "Rename".to_owned(),
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let [table_area, status_area] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(inner)
+        else {
+            unreachable!()
+        };
+
+        self.viewport_height = table_area.height as usize;
+        let height = self.viewport_height.max(1);
+        if self.curr_index < self.offset {
+            self.offset = self.curr_index;
+        } else if self.curr_index >= self.offset + height {
+            self.offset = self.curr_index + 1 - height;
+        }
+        let rows = self.renames.iter().zip(&self.conflicts).enumerate().skip(self.offset).take(height).map(
+            |(i, ((from, to), conflict))| {
+                rename_row(display_name(from), display_name(to), *conflict, i == self.curr_index)
+            },
+        );
+        f.render_widget(Table::new(rows, RENAME_TABLE_WIDTHS), table_area);
+        f.render_widget(Paragraph::new(rename_status(self.renames.len(), &self.conflicts)), status_area);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_names() {
+        let paths = vec![PathBuf::from("/d/a.txt"), PathBuf::from("/d/b.txt"), PathBuf::from("/d/c.txt")];
+        assert_eq!(
+            diff_names(&paths, "b.txt\na.txt\nc.txt\n"),
+            Ok(vec![
+                (PathBuf::from("/d/a.txt"), PathBuf::from("/d/b.txt")),
+                (PathBuf::from("/d/b.txt"), PathBuf::from("/d/a.txt")),
+            ])
+        );
+        assert!(diff_names(&paths, "a.txt\nb.txt\n").is_err());
+        assert!(diff_names(&paths, "a.txt\nsub/b.txt\nc.txt").is_err());
+    }
+
+    #[test]
+    fn test_edit_failed() {
+        let mut bulk_rename = BulkRename::new();
+        let Some(Action::Edit(file, None)) = bulk_rename.edit_names(vec![PathBuf::from("/d/a.txt")]) else {
+            panic!("expected the names to be edited");
+        };
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a.txt\n");
+        #[cfg(unix)]
+        assert_eq!(std::os::unix::fs::PermissionsExt::mode(&file.metadata().unwrap().permissions()) & 0o777, 0o600);
+
+        bulk_rename.update(Action::EditFailed(PathBuf::from("/elsewhere.txt"))).unwrap();
+        assert!(bulk_rename.editing.is_some());
+        bulk_rename.update(Action::EditFailed(file.clone())).unwrap();
+        assert!(bulk_rename.editing.is_none());
+        assert!(!file.exists());
+    }
+}
@@ -0,0 +1,306 @@
+use std::{fs, path::PathBuf};
+
+use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Component, Frame};
+use crate::{action::Action, mode::Mode, services::commands::CommandRunner, utils};
+
+/// Commands kept in the history, the oldest being dropped first.
+const MAX_HISTORY: usize = 1000;
+
+/// `:` line at the bottom of the screen to run shell commands in the working directory, with their output streamed
+/// into the [`Output`](super::output::Output) popup. Up and Down go through the commands run before, which are kept
+/// across restarts, and Tab completes the names of the entries in the listing.
+#[derive(Default)]
+pub struct CommandLine {
+    command_tx: Option<UnboundedSender<Action>>,
+    runner: CommandRunner,
+    mode: Mode,
+    cwd: Option<PathBuf>,
+    input: String,
+    /// Names of the entries in the listing when the command line was opened, those of directories with a trailing
+    /// slash.
+    names: Vec<String>,
+    /// Names the word at the end of the input can be completed to, escaped for the shell, and which of them is in the
+    /// input while Tab cycles through them.
+    completions: Option<(Vec<String>, usize)>,
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    /// Position in the history while going through it, and what had been typed before.
+    history_index: Option<usize>,
+    draft: String,
+}
+
+impl CommandLine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn open(&mut self, names: Vec<String>) -> Option<Action> {
+        self.names = names;
+        self.input.clear();
+        self.completions = None;
+        self.history_index = None;
+        Some(Action::SwitchMode(Mode::Command))
+    }
+
+    fn submit(&mut self) -> Option<Action> {
+        let command = self.input.trim().to_owned();
+        if command.is_empty() {
+            return Some(Action::SwitchMode(Mode::Home));
+        }
+        self.remember(command.clone());
+        let Some(cwd) = &self.cwd else {
+            if let Some(tx) = &self.command_tx {
+                let _ = tx.send(Action::SwitchMode(Mode::Home));
+            }
+            return Some(Action::Error(format!("Can't run {command}: there is no working directory")));
+        };
+        // Switches to the output popup once started.
+        self.runner.stream(&command, cwd);
+        None
+    }
+
+    fn remember(&mut self, command: String) {
+        self.history.retain(|previous| *previous != command);
+        self.history.push(command);
+        let excess = self.history.len().saturating_sub(MAX_HISTORY);
+        self.history.drain(..excess);
+        let Some(path) = &self.history_path else {
+            return;
+        };
+        let result = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| {
+            let mut contents = self.history.join("\n");
+            contents.push('\n');
+            fs::write(path, contents)
+        });
+        if let Err(e) = result {
+            log::error!("Failed to save the command history to {}: {e}", path.display());
+        }
+    }
+
+    /// Goes back (`delta` < 0) or forward through the history, back to what was typed past its end.
+    fn browse_history(&mut self, delta: isize) {
+        let index = match self.history_index {
+            None if delta < 0 && !self.history.is_empty() => {
+                self.draft = std::mem::take(&mut self.input);
+                self.history.len() - 1
+            },
+            None => return,
+            Some(index) if index.checked_add_signed(delta).is_some_and(|index| index < self.history.len()) => {
+                index.saturating_add_signed(delta)
+            },
+            Some(_) if delta < 0 => return,
+            Some(_) => {
+                self.history_index = None;
+                self.input = std::mem::take(&mut self.draft);
+                return;
+            },
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+        self.completions = None;
+    }
+
+    /// Completes the word at the end of the input to the name of an entry, or to as much of the names starting with
+    /// it as they have in common. When that is nothing more, Tab cycles through them.
+    fn complete(&mut self) {
+        if let Some((completions, index)) = &mut self.completions {
+            self.input.truncate(self.input.len() - completions[*index].len());
+            *index = (*index + 1) % completions.len();
+            self.input.push_str(&completions[*index]);
+            return;
+        }
+        let start = word_start(&self.input);
+        let word = unescape(&self.input[start..]);
+        let matches = self.names.iter().filter(|name| name.starts_with(&word)).collect::<Vec<_>>();
+        match matches.as_slice() {
+            [] => {},
+            [name] => {
+                self.input.truncate(start);
+                self.input.push_str(&escape(name));
+                if !name.ends_with('/') {
+                    self.input.push(' ');
+                }
+            },
+            [first, rest @ ..] => {
+                let common = rest.iter().map(|name| common_prefix_len(first, name)).min().unwrap_or_default();
+                self.input.truncate(start);
+                if common > word.len() {
+                    self.input.push_str(&escape(&first[..common]));
+                } else {
+                    let completions = matches.iter().map(|name| escape(name)).collect::<Vec<_>>();
+                    self.input.push_str(&completions[0]);
+                    self.completions = Some((completions, 0));
+                }
+            },
+        }
+    }
+}
+
+/// Index at which the last word of `input` starts, spaces escaped with a backslash being part of the word.
+fn word_start(input: &str) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c.is_whitespace() {
+            start = i + c.len_utf8();
+        }
+    }
+    start
+}
+
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if !c.is_alphanumeric() && !"/._-+=:,@%".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(word: &str) -> String {
+    let mut unescaped = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        unescaped.extend(if c == '\\' { chars.next() } else { Some(c) });
+    }
+    unescaped
+}
+
+/// Length in bytes of the longest prefix `a` and `b` have in common.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(a, b)| a == b).map(|(c, _)| c.len_utf8()).sum()
+}
+
+impl Component for CommandLine {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.runner = CommandRunner::new(tx.clone());
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn init(&mut self, _area: Rect) -> Result<()> {
+        let path = utils::get_data_dir().join("command_history");
+        self.history = fs::read_to_string(&path)
+            .map(|contents| contents.lines().filter(|line| !line.is_empty()).map(str::to_owned).collect())
+            .unwrap_or_default();
+        self.history_path = Some(path);
+        Ok(())
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.mode != Mode::Command || key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return Ok(None);
+        }
+        match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            // Like in vim, backspacing past the start leaves the command line.
+            KeyCode::Backspace if self.input.is_empty() => return Ok(Some(Action::CancelCommandLine)),
+            KeyCode::Backspace => {
+                self.input.pop();
+            },
+            _ => return Ok(None),
+        }
+        self.completions = None;
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::DirectoryChanged(path) => self.cwd = Some(path),
+            Action::OpenCommandLine(names) => return Ok(self.open(names)),
+            // Closing the output of a command that is still running stops it.
+            Action::CancelCommand | Action::CloseOutput => self.runner.cancel(),
+            _ if self.mode != Mode::Command => {},
+            Action::SubmitCommandLine => return Ok(self.submit()),
+            Action::CancelCommandLine => return Ok(Some(Action::SwitchMode(Mode::Home))),
+            Action::CompleteCommandLine => self.complete(),
+            Action::SelectPrevious => self.browse_history(-1),
+            Action::SelectNext => self.browse_history(1),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::Command || area.height == 0 {
+            return Ok(());
+        }
+
+        let area = Rect { y: area.bottom() - 1, height: 1, ..area };
+        f.render_widget(Clear, area);
+        // Only the end of a long command fits.
+        let fitting = (area.width as usize).saturating_sub(2);
+        let skip = self.input.chars().count().saturating_sub(fitting);
+        let input = self.input.chars().skip(skip).collect::<String>();
+        let cycle = match &self.completions {
+            Some((completions, index)) => format!("{}/{}", index + 1, completions.len()).dim(),
+            None => "".into(),
+        };
+        let line =
+            Block::default().title(format!(":{input}▏")).title(block::Title::from(cycle).alignment(Alignment::Right));
+        f.render_widget(line, area);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_complete() {
+        let mut command_line = CommandLine::new();
+        command_line.open(vec!["src/".to_owned(), "my file.txt".to_owned(), "main.rs".to_owned(), "mod.rs".to_owned()]);
+        let mut complete = |input: &str| {
+            command_line.input = input.to_owned();
+            command_line.completions = None;
+            command_line.complete();
+            command_line.input.clone()
+        };
+
+        assert_eq!(complete("ls s"), "ls src/");
+        assert_eq!(complete("cat my"), r"cat my\ file.txt ");
+        assert_eq!(complete(r"cat my\ f"), r"cat my\ file.txt ");
+        assert_eq!(complete("wc -l x"), "wc -l x");
+
+        // Nothing in common past `m`, so Tab cycles through the matches.
+        assert_eq!(complete("wc m"), r"wc my\ file.txt");
+        command_line.complete();
+        assert_eq!(command_line.input, "wc main.rs");
+        command_line.complete();
+        command_line.complete();
+        assert_eq!(command_line.input, r"wc my\ file.txt");
+
+        command_line.input = "wc ma".to_owned();
+        command_line.completions = None;
+        command_line.complete();
+        assert_eq!(command_line.input, "wc main.rs ");
+    }
+
+    #[test]
+    fn test_submit_without_cwd() {
+        let mut command_line = CommandLine::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        command_line.register_action_handler(tx).unwrap();
+        command_line.open(vec![]);
+        command_line.input = "ls".to_owned();
+
+        assert!(matches!(command_line.submit(), Some(Action::Error(_))));
+        assert_eq!(rx.try_recv().ok(), Some(Action::SwitchMode(Mode::Home)));
+    }
+}
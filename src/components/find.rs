@@ -0,0 +1,226 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{centered_rect, Component, Frame};
+use crate::{
+    action::Action,
+    config::Config,
+    filter::{FilterKind, Matcher},
+    mode::Mode,
+    services::find::{FindOptions, Finder},
+};
+
+/// Popup that searches the tree below the working directory for file names matching a pattern, and jumps to the
+/// directory of the picked result.
+#[derive(Default)]
+pub struct Find {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    finder: Finder,
+    mode: Mode,
+    root: PathBuf,
+    kind: FilterKind,
+    pattern: String,
+    /// Compiled pattern, used to highlight the matches. `None` while the pattern is empty or doesn't compile.
+    matcher: Option<Arc<Matcher>>,
+    error: Option<String>,
+    results: Vec<PathBuf>,
+    /// Id of the search that is still streaming results in, if any.
+    search: Option<usize>,
+    curr_index: usize,
+    offset: usize,
+    viewport_height: usize,
+}
+
+impl Find {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn restart_search(&mut self) {
+        self.finder.cancel();
+        self.search = None;
+        self.results.clear();
+        self.curr_index = 0;
+        self.offset = 0;
+        self.error = None;
+        self.matcher = None;
+        if self.pattern.is_empty() {
+            return;
+        }
+        let matcher = match Matcher::new(self.kind, &self.pattern) {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            },
+        };
+        // Shared with the search rather than compiled twice, the results using it for highlighting.
+        let matcher = Arc::new(matcher);
+        self.matcher = Some(matcher.clone());
+        let options =
+            FindOptions { show_hidden: self.config.show_hidden, respect_gitignore: self.config.respect_gitignore };
+        self.search = Some(self.finder.find(&self.root, matcher, options));
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        self.curr_index = self.curr_index.saturating_add_signed(delta).min(self.results.len().saturating_sub(1));
+    }
+
+    fn close(&mut self) -> Option<Action> {
+        self.finder.cancel();
+        self.search = None;
+        Some(Action::SwitchMode(Mode::Home))
+    }
+
+    fn reveal_selected(&mut self) -> Option<Action> {
+        let path = self.results.get(self.curr_index)?.clone();
+        if let Some(tx) = &self.command_tx {
+            tx.send(Action::Reveal(path)).ok()?;
+        }
+        self.close()
+    }
+
+    fn result_line(&self, path: &Path) -> Line<'static> {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let file_name = relative.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let parent = relative.parent().map(|parent| parent.display().to_string()).unwrap_or_default();
+        let mut spans = vec![];
+        if !parent.is_empty() {
+            spans.push(Span::raw(format!("{parent}{}", std::path::MAIN_SEPARATOR)).dim());
+        }
+        let highlighted = self.matcher.as_ref().and_then(|matcher| matcher.matches(&file_name)).unwrap_or_default();
+        spans.extend(file_name.chars().enumerate().map(|(i, c)| {
+            if highlighted.indices.contains(&i) {
+                Span::styled(c.to_string(), Style::new().fg(Color::Yellow).bold())
+            } else {
+                Span::raw(c.to_string())
+            }
+        }));
+        Line::from(spans)
+    }
+}
+
+impl Component for Find {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.finder = Finder::new(tx.clone());
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.mode != Mode::Find || key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return Ok(None);
+        }
+        match key.code {
+            KeyCode::Char(c) => {
+                self.pattern.push(c);
+                self.restart_search();
+            },
+            KeyCode::Backspace => {
+                self.pattern.pop();
+                self.restart_search();
+            },
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::DirectoryChanged(path) => self.root = path,
+            Action::ToggleHidden => self.config.show_hidden = !self.config.show_hidden,
+            Action::OpenFind => {
+                self.restart_search();
+                return Ok(Some(Action::SwitchMode(Mode::Find)));
+            },
+            Action::FindResults(id, paths) if self.search == Some(id) => self.results.extend(paths),
+            Action::FindDone(id) if self.search == Some(id) => self.search = None,
+            _ if self.mode != Mode::Find => {},
+            Action::CloseFind => return Ok(self.close()),
+            Action::Open => return Ok(self.reveal_selected()),
+            Action::CycleFilterKind => {
+                self.kind = self.kind.next();
+                self.restart_search();
+            },
+            Action::SelectNext => self.move_cursor(1),
+            Action::SelectPrevious => self.move_cursor(-1),
+            Action::PageDown => self.move_cursor(self.viewport_height as isize),
+            Action::PageUp => self.move_cursor(-(self.viewport_height as isize)),
+            Action::SelectFirst => self.curr_index = 0,
+            Action::SelectLast => self.move_cursor(isize::MAX),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::Find {
+            return Ok(());
+        }
+
+        let area = centered_rect(80, 80, area);
+        f.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title(format!("Find in {}", self.root.display()));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let [input_area, results_area, status_area] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)])
+            .split(inner)
+        else {
+            unreachable!()
+        };
+
+        f.render_widget(Paragraph::new(format!("> {}▏", self.pattern)), input_area);
+
+        self.viewport_height = results_area.height as usize;
+        let height = self.viewport_height.max(1);
+        if self.curr_index < self.offset {
+            self.offset = self.curr_index;
+        } else if self.curr_index >= self.offset + height {
+            self.offset = self.curr_index + 1 - height;
+        }
+        let lines = self
+            .results
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(self.viewport_height)
+            .map(|(i, path)| {
+                let line = self.result_line(path);
+                if i == self.curr_index {
+                    line.style(Style::new().bg(Color::Magenta))
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        f.render_widget(Paragraph::new(lines), results_area);
+
+        let status = match &self.error {
+            Some(error) => Line::from(error.clone().red()),
+            None => {
+                let searching = if self.search.is_some() { "searching… " } else { "" };
+                Line::from(format!("[{}] {searching}{} results", self.kind, self.results.len()).dim())
+            },
+        };
+        f.render_widget(Paragraph::new(status), status_area);
+
+        Ok(())
+    }
+}
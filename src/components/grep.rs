@@ -0,0 +1,282 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+use regex::RegexBuilder;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{centered_rect, Component, Frame};
+use crate::{
+    action::Action,
+    config::Config,
+    mode::Mode,
+    services::{
+        find::FindOptions,
+        grep::{GrepMatch, Grepper, MAX_MATCHES},
+    },
+};
+
+/// Popup that searches the contents of the files below the working directory and opens the editor at the picked
+/// match.
+#[derive(Default)]
+pub struct Grep {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    grepper: Grepper,
+    mode: Mode,
+    root: PathBuf,
+    query: String,
+    /// Treat the query as a regular expression rather than a literal string.
+    regex: bool,
+    /// Whether the query changed since the search was last started, in which case `Enter` starts a new search
+    /// instead of opening the selected match.
+    dirty: bool,
+    error: Option<String>,
+    results: Vec<GrepMatch>,
+    /// Id of the search that is still streaming results in, if any.
+    search: Option<usize>,
+    /// Whether the search stopped at [`MAX_MATCHES`], leaving out matches past them.
+    truncated: bool,
+    curr_index: usize,
+    offset: usize,
+    viewport_height: usize,
+}
+
+impl Grep {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn start_search(&mut self) {
+        self.grepper.cancel();
+        self.search = None;
+        self.truncated = false;
+        self.dirty = false;
+        self.error = None;
+        self.results.clear();
+        self.curr_index = 0;
+        self.offset = 0;
+        if self.query.is_empty() {
+            return;
+        }
+        let pattern = if self.regex { self.query.clone() } else { regex::escape(&self.query) };
+        let case_sensitive = self.query.chars().any(char::is_uppercase);
+        let regex = match RegexBuilder::new(&pattern).case_insensitive(!case_sensitive).build() {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            },
+        };
+        let options =
+            FindOptions { show_hidden: self.config.show_hidden, respect_gitignore: self.config.respect_gitignore };
+        self.search = Some(self.grepper.search(&self.root, regex, self.config.grep_max_file_size, options));
+    }
+
+    fn cancel_search(&mut self) {
+        self.grepper.cancel();
+        self.search = None;
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        self.curr_index = self.curr_index.saturating_add_signed(delta).min(self.results.len().saturating_sub(1));
+    }
+
+    fn open_selected(&mut self) -> Option<Action> {
+        if self.dirty {
+            self.start_search();
+            return None;
+        }
+        let found = self.results.get(self.curr_index)?;
+        let edit = Action::Edit(found.path.clone(), Some(found.line_number));
+        if let Some(tx) = &self.command_tx {
+            tx.send(Action::SwitchMode(Mode::Home)).ok()?;
+        }
+        self.cancel_search();
+        Some(edit)
+    }
+
+    fn result_line(&self, found: &GrepMatch) -> Line<'static> {
+        let relative = found.path.strip_prefix(&self.root).unwrap_or(&found.path);
+        let mut spans = vec![
+            Span::raw(relative.display().to_string()).fg(Color::Cyan),
+            Span::raw(format!(":{}: ", found.line_number)).dim(),
+        ];
+        spans.extend(highlight(&found.line, &found.ranges));
+        Line::from(spans)
+    }
+
+    fn context_lines(found: &GrepMatch) -> Vec<Line<'static>> {
+        let width = (found.line_number + found.context_after.len()).to_string().len();
+        let first_line = found.line_number - found.context_before.len();
+        let dim_line = |i: usize, line: &str| Line::from(format!("{:>width$} {line}", first_line + i)).dim();
+        let mut lines = found.context_before.iter().enumerate().map(|(i, line)| dim_line(i, line)).collect::<Vec<_>>();
+        let mut spans = vec![Span::raw(format!("{:>width$} ", found.line_number)).bold()];
+        spans.extend(highlight(&found.line, &found.ranges));
+        lines.push(Line::from(spans));
+        lines.extend(
+            found.context_after.iter().enumerate().map(|(i, line)| dim_line(found.context_before.len() + 1 + i, line)),
+        );
+        lines
+    }
+}
+
+/// Splits `line` into spans, with the byte `ranges` highlighted.
+fn highlight(line: &str, ranges: &[(usize, usize)]) -> Vec<Span<'static>> {
+    let mut spans = vec![];
+    let mut last = 0;
+    for &(start, end) in ranges {
+        spans.push(Span::raw(line[last..start].to_string()));
+        spans.push(Span::styled(line[start..end].to_string(), Style::new().fg(Color::Yellow).bold()));
+        last = end;
+    }
+    spans.push(Span::raw(line[last..].to_string()));
+    spans
+}
+
+impl Component for Grep {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.grepper = Grepper::new(tx.clone());
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.mode != Mode::Grep || key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return Ok(None);
+        }
+        match key.code {
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.dirty = true;
+            },
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.dirty = true;
+            },
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::DirectoryChanged(path) => {
+                self.root = path;
+                self.dirty = true;
+            },
+            Action::ToggleHidden => {
+                self.config.show_hidden = !self.config.show_hidden;
+                self.dirty = true;
+            },
+            Action::OpenGrep => return Ok(Some(Action::SwitchMode(Mode::Grep))),
+            Action::GrepResults(id, results) if self.search == Some(id) => self.results.extend(results),
+            Action::GrepDone(id, truncated) if self.search == Some(id) => {
+                self.search = None;
+                self.truncated = truncated;
+            },
+            _ if self.mode != Mode::Grep => {},
+            Action::CancelGrep => self.cancel_search(),
+            Action::CloseGrep => {
+                self.cancel_search();
+                return Ok(Some(Action::SwitchMode(Mode::Home)));
+            },
+            Action::ToggleGrepRegex => {
+                self.regex = !self.regex;
+                self.dirty = true;
+            },
+            Action::Open => return Ok(self.open_selected()),
+            Action::SelectNext => self.move_cursor(1),
+            Action::SelectPrevious => self.move_cursor(-1),
+            Action::PageDown => self.move_cursor(self.viewport_height as isize),
+            Action::PageUp => self.move_cursor(-(self.viewport_height as isize)),
+            Action::SelectFirst => self.curr_index = 0,
+            Action::SelectLast => self.move_cursor(isize::MAX),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::Grep {
+            return Ok(());
+        }
+
+        let area = centered_rect(90, 90, area);
+        f.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title(format!("Search contents of {}", self.root.display()));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let [input_area, results_area, context_area, status_area] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Min(0),
+                Constraint::Length(2 + 2 * 2 + 1),
+                Constraint::Length(1),
+            ])
+            .split(inner)
+        else {
+            unreachable!()
+        };
+
+        let kind = if self.regex { "regex" } else { "literal" };
+        f.render_widget(Paragraph::new(format!("{kind}> {}▏", self.query)), input_area);
+
+        self.viewport_height = results_area.height as usize;
+        let height = self.viewport_height.max(1);
+        if self.curr_index < self.offset {
+            self.offset = self.curr_index;
+        } else if self.curr_index >= self.offset + height {
+            self.offset = self.curr_index + 1 - height;
+        }
+        let lines = self
+            .results
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(self.viewport_height)
+            .map(|(i, found)| {
+                let line = self.result_line(found);
+                if i == self.curr_index {
+                    line.style(Style::new().bg(Color::Magenta))
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        f.render_widget(Paragraph::new(lines), results_area);
+
+        if let Some(found) = self.results.get(self.curr_index) {
+            let context = Paragraph::new(Self::context_lines(found)).block(Block::default().borders(Borders::TOP));
+            f.render_widget(context, context_area);
+        }
+
+        let status = match &self.error {
+            Some(error) => Line::from(error.clone().red()),
+            None if self.dirty => Line::from("press Enter to search".dim()),
+            None => {
+                let searching = if self.search.is_some() { "searching… " } else { "" };
+                let files = {
+                    let mut paths = self.results.iter().map(|found| &found.path).collect::<Vec<_>>();
+                    paths.dedup();
+                    paths.len()
+                };
+                let truncated =
+                    if self.truncated { ", stopped there (narrow the search down to see more)" } else { "" };
+                Line::from(format!("{searching}{} matches in {files} files{truncated}", self.results.len()).dim())
+            },
+        };
+        f.render_widget(Paragraph::new(status), status_area);
+
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
 use std::{
     cmp::min,
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     default,
     hash::Hash,
-    path::Path,
-    time::Duration,
+    ops::RangeInclusive,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    time::{Duration, Instant},
     vec,
 };
 
@@ -14,39 +15,659 @@ use ratatui::{prelude::*, widgets::*};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 
-use super::{Component, Frame};
+use super::{
+    main_layout,
+    prompt::{Choice, PromptPurpose},
+    Component, Frame, MainLayout,
+};
 use crate::{
     action::Action,
+    column::{self, Column},
     config::{Config, KeyBindings},
+    filter::{FilterKind, Matcher},
+    mode::Mode,
+    services::{
+        commands::{CommandRunner, Placeholders},
+        fileops::{self, Conflict, FileOp},
+        journal::{Change, Journal, JournalEntry, Replay},
+        listing::{DirEntry, Lister},
+        opener::Opener,
+    },
+    sort::{SortKey, SortSpec},
+    utils,
 };
 
 #[derive(Default, Clone)]
-struct DirEntry {
-    path: String,
-    is_dir: bool,
-    size: Option<usize>,
+struct WorkingDirectory {
+    path: PathBuf,
+    children: Vec<DirEntry>,
+    /// Entry the cursor was on when we navigated away, restored when coming back through the history.
+    selection: Option<PathBuf>,
+    /// Id of the listing that is still streaming children in, if any.
+    listing: Option<usize>,
+    /// Entries marked for a bulk operation. Kept in the history along with the directory.
+    marked: HashSet<PathBuf>,
 }
 
-#[derive(Default, Clone)]
-struct WorkingDirectory {
-    path: String,
-    children: Vec<Option<DirEntry>>,
+impl WorkingDirectory {
+    fn new(path: PathBuf) -> Self {
+        Self { path, ..Self::default() }
+    }
+
+    fn is_loading(&self) -> bool {
+        self.listing.is_some()
+    }
 }
 
 #[derive(Default)]
 pub struct Home {
     command_tx: Option<UnboundedSender<Action>>,
     config: Config,
+    lister: Lister,
+    opener: Opener,
+    runner: CommandRunner,
     cwd: Option<WorkingDirectory>,
     history_backward: Vec<WorkingDirectory>,
     history_forward: Vec<WorkingDirectory>,
     curr_index: Option<usize>,
-    selected: bool,
+    /// Index of the first entry shown in the viewport.
+    offset: usize,
+    /// Number of rows the listing had when it was last drawn.
+    viewport_height: usize,
+    /// Entry to put the cursor on once it shows up in the listing, e.g. the directory we just came up from.
+    pending_selection: Option<PathBuf>,
+    /// Sort order picked for each directory the user changed it in; the others use the default.
+    sort_specs: HashMap<PathBuf, SortSpec>,
+    /// Whether children were added since the listing was last sorted.
+    unsorted: bool,
+    /// Indices into the children of the working directory of the entries that are shown, in order. The cursor and the
+    /// viewport index into this rather than into the children directly.
+    visible: Vec<usize>,
+    filter: Option<Filter>,
+    mode: Mode,
+    show_hidden: bool,
+    show_preview: bool,
+    /// Entry last announced through [`Action::SelectionChanged`].
+    announced_selection: Option<PathBuf>,
+    /// Last error reported through [`Action::Error`] and when it was, shown in the status line for a while.
+    error: Option<(String, Instant)>,
+    /// Background jobs still running, and those of them waiting for the user after a failure.
+    jobs: HashSet<usize>,
+    failed_jobs: HashSet<usize>,
+    /// Entry the visual range was started on. The range spans from it to the cursor.
+    visual_anchor: Option<PathBuf>,
+    register: Option<Register>,
+    journal: Journal,
+    /// Whether an undo or a redo is running, to keep them from overlapping.
+    replaying: bool,
+}
+
+/// Entries yanked or cut, waiting to be pasted in another directory. Unlike the marks it doesn't belong to a
+/// directory, so it stays around while navigating.
+struct Register {
+    paths: Vec<PathBuf>,
+    cut: bool,
+}
+
+/// How long an error stays in the status line.
+const ERROR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Narrows the listing down to the entries whose name matches a pattern.
+#[derive(Default)]
+struct Filter {
+    kind: FilterKind,
+    pattern: String,
+    /// `None` while the pattern is empty or doesn't compile.
+    matcher: Option<Matcher>,
+    error: Option<String>,
+}
+
+impl Filter {
+    fn recompile(&mut self) {
+        self.matcher = None;
+        self.error = None;
+        if self.pattern.is_empty() {
+            return;
+        }
+        match Matcher::new(self.kind, &self.pattern) {
+            Ok(matcher) => self.matcher = Some(matcher),
+            Err(e) => self.error = Some(e),
+        }
+    }
 }
 
 impl Home {
-    pub fn new() -> Self {
-        Self { cwd: Some(WorkingDirectory { path: "\\".to_string(), children: vec![] }), ..Self::default() }
+    pub fn new(path: Option<PathBuf>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None => std::env::current_dir()?,
+        };
+        let path = path.canonicalize()?;
+        Ok(Self { cwd: Some(WorkingDirectory::new(path)), ..Self::default() })
+    }
+
+    fn selected_entry(&self) -> Option<&DirEntry> {
+        self.cwd.as_ref()?.children.get(*self.visible.get(self.curr_index?)?)
+    }
+
+    /// Takes the working directory out, remembering the selection so it can be restored from the history.
+    fn leave_cwd(&mut self) -> WorkingDirectory {
+        self.lister.cancel();
+        let selection = self.selected_entry().map(|entry| entry.path.clone());
+        let mut cwd = self.cwd.take().unwrap();
+        cwd.selection = selection;
+        cwd.children = vec![];
+        cwd.listing = None;
+        cwd
+    }
+
+    fn enter_cwd(&mut self, mut cwd: WorkingDirectory) {
+        self.filter = None;
+        self.pending_selection = cwd.selection.take();
+        self.curr_index = None;
+        self.offset = 0;
+        self.cwd = Some(cwd);
+        self.refresh_listing();
+        self.announce_cwd();
+    }
+
+    fn announce_cwd(&self) {
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.send(Action::DirectoryChanged(self.cwd.as_ref().unwrap().path.clone()));
+        }
+    }
+
+    /// Tells the other components about the selected entry if it changed since last time.
+    fn announce_selection(&mut self) {
+        let selection = self.selected_entry().map(|entry| entry.path.clone());
+        if selection != self.announced_selection {
+            self.announced_selection.clone_from(&selection);
+            if let Some(tx) = &self.command_tx {
+                let _ = tx.send(Action::SelectionChanged(selection));
+            }
+        }
+    }
+
+    /// Throws away the children of the working directory and starts listing it again in the background.
+    fn refresh_listing(&mut self) {
+        let cwd = self.cwd.as_mut().unwrap();
+        cwd.children.clear();
+        if let Some(parent) = cwd.path.parent() {
+            cwd.children.push(DirEntry {
+                path: parent.to_path_buf(),
+                name: "..".to_owned(),
+                is_dir: true,
+                ..DirEntry::default()
+            });
+        }
+        cwd.listing = Some(self.lister.list(&cwd.path));
+        self.update_visible();
+    }
+
+    fn change_directory(&mut self, path: PathBuf, selection: Option<PathBuf>) {
+        let cwd = self.leave_cwd();
+        self.history_backward.push(cwd);
+        self.history_forward.clear();
+        self.enter_cwd(WorkingDirectory { selection, ..WorkingDirectory::new(path) });
+    }
+
+    fn go_to_parent(&mut self) {
+        let cwd = self.cwd.as_ref().unwrap().path.clone();
+        if let Some(parent) = cwd.parent() {
+            self.change_directory(parent.to_path_buf(), Some(cwd));
+        }
+    }
+
+    /// Goes to the directory containing `path` and puts the cursor on it.
+    fn reveal(&mut self, path: PathBuf) {
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        if parent == self.cwd.as_ref().unwrap().path {
+            self.filter = None;
+            self.apply_filter();
+            match self.position_of(&path) {
+                Some(i) => self.curr_index = Some(i),
+                None => self.pending_selection = Some(path),
+            }
+        } else {
+            self.change_directory(parent.to_path_buf(), Some(path));
+        }
+    }
+
+    fn open_selected(&mut self) {
+        let Some(dir_entry) = self.selected_entry() else {
+            return;
+        };
+        if !dir_entry.is_dir {
+            // The marked files go along with the selected one if it is one of them.
+            let selected = dir_entry.path.clone();
+            let mut paths = self.operands();
+            if !paths.contains(&selected) {
+                paths = vec![selected];
+            }
+            paths.retain(|path| !path.is_dir());
+            self.opener.open(paths);
+            return;
+        }
+        if dir_entry.name == ".." {
+            self.go_to_parent();
+        } else {
+            let path = dir_entry.path.clone();
+            self.change_directory(path, None);
+        }
+    }
+
+    fn history_back(&mut self) {
+        if let Some(history_item) = self.history_backward.pop() {
+            let cwd = self.leave_cwd();
+            self.history_forward.push(cwd);
+            self.enter_cwd(history_item);
+        }
+    }
+
+    fn history_forward(&mut self) {
+        if let Some(history_item) = self.history_forward.pop() {
+            let cwd = self.leave_cwd();
+            self.history_backward.push(cwd);
+            self.enter_cwd(history_item);
+        }
+    }
+
+    /// Moves the cursor by `delta` entries, stopping at either end of the listing.
+    fn move_cursor(&mut self, delta: isize) {
+        let len = self.visible.len();
+        if let Some(curr_index) = self.curr_index {
+            self.curr_index = Some(curr_index.saturating_add_signed(delta).min(len.saturating_sub(1)));
+        }
+    }
+
+    /// Adjusts the viewport so that the entry at `index` is visible, scrolling as little as possible.
+    fn scroll_to(&mut self, index: usize) {
+        let len = self.visible.len();
+        let height = self.viewport_height.max(1);
+        if index < self.offset {
+            self.offset = index;
+        } else if index >= self.offset + height {
+            self.offset = index + 1 - height;
+        }
+        // Don't leave empty rows at the bottom when the listing shrinks or the terminal grows.
+        self.offset = self.offset.min(len.saturating_sub(height));
+    }
+
+    fn on_listing_batch(&mut self, id: usize, entries: &[DirEntry]) {
+        let cwd = self.cwd.as_mut().unwrap();
+        if cwd.listing != Some(id) {
+            return;
+        }
+        cwd.children.extend_from_slice(entries);
+        // Batches can arrive much faster than frames are drawn, so sorting is left to `settle_children`.
+        self.unsorted = true;
+    }
+
+    /// Sorts the children that streamed in since the last call and puts the cursor on the pending selection once it
+    /// has shown up.
+    fn settle_children(&mut self) {
+        if !self.unsorted {
+            return;
+        }
+        self.unsorted = false;
+        self.sort_children();
+        if let Some(i) = self.pending_selection.as_deref().and_then(|pending| self.position_of(pending)) {
+            self.curr_index = Some(i);
+            self.pending_selection = None;
+        }
+    }
+
+    fn position_of(&self, path: &Path) -> Option<usize> {
+        let children = &self.cwd.as_ref()?.children;
+        self.visible.iter().position(|&i| children[i].path == path)
+    }
+
+    fn sort_spec(&self) -> SortSpec {
+        self.sort_specs.get(&self.cwd.as_ref().unwrap().path).copied().unwrap_or_default()
+    }
+
+    fn update_sort_spec(&mut self, f: impl FnOnce(&mut SortSpec)) {
+        let mut sort_spec = self.sort_spec();
+        f(&mut sort_spec);
+        self.sort_specs.insert(self.cwd.as_ref().unwrap().path.clone(), sort_spec);
+        self.sort_children();
+    }
+
+    /// Sorts the children of the working directory, keeping the cursor on the same entry.
+    fn sort_children(&mut self) {
+        let sort_spec = self.sort_spec();
+        let selected = self.selected_entry().map(|entry| entry.path.clone());
+        let cwd = self.cwd.as_mut().unwrap();
+        // The `..` entry stays on top.
+        let first = usize::from(cwd.children.first().is_some_and(|child| child.name == ".."));
+        sort_spec.sort(&mut cwd.children[first..]);
+        self.update_visible();
+        if let Some(selected) = selected {
+            self.curr_index = self.position_of(&selected);
+        }
+    }
+
+    /// Recomputes which children are shown after the children, the filter or whether dotfiles are shown changed.
+    fn update_visible(&mut self) {
+        let children = &self.cwd.as_ref().unwrap().children;
+        let matcher = self.filter.as_ref().and_then(|filter| filter.matcher.as_ref());
+        let show_hidden = self.show_hidden;
+        self.visible = children
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| {
+                if child.name == ".." {
+                    return matcher.is_none();
+                }
+                (show_hidden || !child.name.starts_with('.'))
+                    && matcher.is_none_or(|matcher| matcher.matches(&child.name).is_some())
+            })
+            .map(|(i, _)| i)
+            .collect();
+    }
+
+    /// Applies a changed filter and puts the cursor on the entry that matches best.
+    fn apply_filter(&mut self) {
+        let selected = self.selected_entry().map(|entry| entry.path.clone());
+        self.update_visible();
+        let children = &self.cwd.as_ref().unwrap().children;
+        let best_match = self.filter.as_ref().and_then(|filter| filter.matcher.as_ref()).and_then(|matcher| {
+            self.visible
+                .iter()
+                .enumerate()
+                .filter_map(|(position, &i)| Some((position, matcher.matches(&children[i].name)?.score)))
+                // `max_by_key` picks the last of equal scores, so reverse to prefer the first entry.
+                .rev()
+                .max_by_key(|(_, score)| *score)
+                .map(|(position, _)| position)
+        });
+        self.curr_index = best_match.or_else(|| selected.and_then(|selected| self.position_of(&selected)));
+    }
+
+    fn edit_filter(&mut self, f: impl FnOnce(&mut Filter)) {
+        let filter = self.filter.get_or_insert_with(Filter::default);
+        f(filter);
+        filter.recompile();
+        self.apply_filter();
+    }
+
+    /// Entries file operations act on: the marked ones in listing order, or else the selected one unless it is `..`.
+    fn operands(&self) -> Vec<PathBuf> {
+        let cwd = self.cwd.as_ref().unwrap();
+        if !cwd.marked.is_empty() {
+            return cwd
+                .children
+                .iter()
+                .filter(|child| cwd.marked.contains(&child.path))
+                .map(|child| child.path.clone())
+                .collect();
+        }
+        self.selected_entry()
+            .filter(|entry| entry.name != "..")
+            .map(|entry| vec![entry.path.clone()])
+            .unwrap_or_default()
+    }
+
+    /// Starts the command `name` from the config on the selected or marked entries.
+    fn run_command(&self, name: &str) -> Option<Action> {
+        let Some(command) = self.config.commands.get(name) else {
+            return Some(Action::Error(format!("No command is named {name}")));
+        };
+        let dir = &self.cwd.as_ref().unwrap().path;
+        let file = self.selected_entry().filter(|entry| entry.name != "..").map(|entry| entry.path.as_path());
+        let files = self.operands();
+        match command.expand(&Placeholders { file, files: &files, dir }) {
+            Ok(expanded) => self.runner.run(name, expanded, command.output, dir),
+            Err(e) => return Some(Action::Error(format!("Can't run {name}: {e}"))),
+        }
+        None
+    }
+
+    /// Visible entries that can be marked, that is all but `..`.
+    fn markable(&self) -> impl Iterator<Item = &DirEntry> {
+        let cwd = self.cwd.as_ref().unwrap();
+        self.visible.iter().map(|&i| &cwd.children[i]).filter(|child| child.name != "..")
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(path) = self.selected_entry().filter(|entry| entry.name != "..").map(|entry| entry.path.clone())
+        else {
+            return;
+        };
+        let marked = &mut self.cwd.as_mut().unwrap().marked;
+        if !marked.remove(&path) {
+            marked.insert(path);
+        }
+        self.move_cursor(1);
+    }
+
+    fn mark_all(&mut self) {
+        let paths = self.markable().map(|entry| entry.path.clone()).collect::<Vec<_>>();
+        self.cwd.as_mut().unwrap().marked.extend(paths);
+    }
+
+    /// Flips the marks of the visible entries.
+    fn invert_marks(&mut self) {
+        let paths = self.markable().map(|entry| entry.path.clone()).collect::<Vec<_>>();
+        let marked = &mut self.cwd.as_mut().unwrap().marked;
+        for path in paths {
+            if !marked.remove(&path) {
+                marked.insert(path);
+            }
+        }
+    }
+
+    /// Positions in the visible entries covered by the visual range.
+    fn visual_range(&self) -> Option<RangeInclusive<usize>> {
+        let anchor = self.position_of(self.visual_anchor.as_deref()?)?;
+        let curr_index = self.curr_index?;
+        Some(min(anchor, curr_index)..=anchor.max(curr_index))
+    }
+
+    /// Marks the entries in the visual range and leaves visual mode.
+    fn accept_visual(&mut self) {
+        if let Some(range) = self.visual_range() {
+            let cwd = self.cwd.as_ref().unwrap();
+            let paths = self.visible[range]
+                .iter()
+                .map(|&i| &cwd.children[i])
+                .filter(|child| child.name != "..")
+                .map(|child| child.path.clone())
+                .collect::<Vec<_>>();
+            self.cwd.as_mut().unwrap().marked.extend(paths);
+        }
+        self.visual_anchor = None;
+    }
+
+    /// Asks for what a file operation needs to know before it can run: a name, a destination or a confirmation.
+    fn prepare_file_op(&self, action: Action) -> Option<Action> {
+        let cwd = &self.cwd.as_ref().unwrap().path;
+        let operands = self.operands();
+        let action = match action {
+            Action::CreateDir => Action::Prompt(PromptPurpose::CreateDir, String::new()),
+            Action::CreateFile => Action::Prompt(PromptPurpose::CreateFile, String::new()),
+            _ if operands.is_empty() => return None,
+            Action::Rename => {
+                match operands.as_slice() {
+                    [path] => Action::Prompt(PromptPurpose::Rename(path.clone()), fileops::display_name(path)),
+                    // Several entries can't take the same new name, so they are renamed with a pattern instead.
+                    _ => Action::OpenPatternRename(operands),
+                }
+            },
+            Action::Copy => {
+                Action::Prompt(PromptPurpose::CopyTo(operands), format!("{}{MAIN_SEPARATOR}", cwd.display()))
+            },
+            Action::Move => {
+                Action::Prompt(PromptPurpose::MoveTo(operands), format!("{}{MAIN_SEPARATOR}", cwd.display()))
+            },
+            Action::BulkRename => Action::EditNames(operands),
+            Action::PatternRename => Action::OpenPatternRename(operands),
+            Action::Trash => Action::FileOp(FileOp::Trash { paths: operands }),
+            Action::Delete => {
+                let op = FileOp::Delete { paths: operands };
+                Action::Confirm(format!("{} permanently?", op.describe()), Box::new(Action::FileOp(op)))
+            },
+            _ => return None,
+        };
+        Some(action)
+    }
+
+    /// Puts the entries file operations would act on in the register, to be copied (or moved if `cut`) by a paste.
+    fn fill_register(&mut self, cut: bool) {
+        let paths = self.operands();
+        if paths.is_empty() {
+            return;
+        }
+        self.cwd.as_mut().unwrap().marked.clear();
+        self.register = Some(Register { paths, cut });
+    }
+
+    /// Drops the cut entries that `entry` moved from the register, as they are gone from where it says. Those that
+    /// failed to move or were skipped stay in it, to be pasted again.
+    fn forget_moved(&mut self, entry: &JournalEntry) {
+        let Some(register) = self.register.as_mut().filter(|register| register.cut) else {
+            return;
+        };
+        let moved = entry
+            .changes
+            .iter()
+            .filter_map(|change| {
+                match change {
+                    Change::Moved { from, .. } => Some(from),
+                    _ => None,
+                }
+            })
+            .collect::<HashSet<_>>();
+        register.paths.retain(|path| !moved.contains(path));
+        if register.paths.is_empty() {
+            self.register = None;
+        }
+    }
+
+    /// Copies or moves the register to the working directory, asking what to do about entries that are already
+    /// there.
+    fn paste(&self) -> Option<Action> {
+        let Register { paths, cut } = self.register.as_ref()?;
+        let dest = &self.cwd.as_ref().unwrap().path;
+        let op = |conflict| {
+            let (sources, dest) = (paths.clone(), dest.clone());
+            let op =
+                if *cut { FileOp::Move { sources, dest, conflict } } else { FileOp::Copy { sources, dest, conflict } };
+            Action::FileOp(op)
+        };
+        let conflicts = paths
+            .iter()
+            .filter(|path| fileops::target_path(path, dest, paths.len()).symlink_metadata().is_ok())
+            .count();
+        if conflicts == 0 {
+            return Some(op(Conflict::Fail));
+        }
+        let message = match paths.len() {
+            1 => format!("{} already exists here", fileops::display_name(&paths[0])),
+            n => format!("{conflicts} of {n} items already exist here"),
+        };
+        Some(Action::Choose(message, vec![
+            Choice::new('r', "rename", op(Conflict::Rename)),
+            Choice::new('s', "skip", op(Conflict::Skip)),
+            Choice::new('o', "overwrite", op(Conflict::Overwrite)),
+        ]))
+    }
+
+    /// Turns the answer to a prompt opened by [`Self::prepare_file_op`] into the file operation to run. Relative paths
+    /// are taken from the working directory.
+    fn on_prompt_submitted(&mut self, purpose: PromptPurpose, input: String) -> Option<Action> {
+        if input.is_empty() {
+            return None;
+        }
+        let cwd = &self.cwd.as_ref().unwrap().path;
+        let op = match purpose {
+            PromptPurpose::Rename(from) => {
+                if input.contains(std::path::is_separator) {
+                    return Some(Action::Error(format!("Invalid name: {input}")));
+                }
+                let to = from.with_file_name(&input);
+                self.pending_selection = Some(to.clone());
+                FileOp::Rename { from, to }
+            },
+            PromptPurpose::CreateDir => {
+                let path = cwd.join(&input);
+                self.pending_selection = Some(path.clone());
+                FileOp::CreateDir(path)
+            },
+            PromptPurpose::CreateFile => {
+                let path = cwd.join(&input);
+                self.pending_selection = Some(path.clone());
+                FileOp::CreateFile(path)
+            },
+            PromptPurpose::CopyTo(sources) => {
+                FileOp::Copy { sources, dest: cwd.join(&input), conflict: Conflict::Fail }
+            },
+            PromptPurpose::MoveTo(sources) => {
+                FileOp::Move { sources, dest: cwd.join(&input), conflict: Conflict::Fail }
+            },
+            // Handled by the preview.
+            PromptPurpose::JumpToOffset(_) => return None,
+        };
+        Some(Action::FileOp(op))
+    }
+
+    /// Undoes or redoes the last file operation on the blocking thread pool.
+    fn replay(&mut self, direction: Replay) -> Option<Action> {
+        if self.replaying {
+            return None;
+        }
+        let Some(mut entry) = self.journal.pop(direction) else {
+            let nothing = match direction {
+                Replay::Undo => "Nothing to undo",
+                Replay::Redo => "Nothing to redo",
+            };
+            return Some(Action::Error(nothing.to_owned()));
+        };
+        let tx = self.command_tx.clone()?;
+        self.replaying = true;
+        tokio::task::spawn_blocking(move || {
+            let result = entry.apply(direction).map_err(|e| e.to_string());
+            let _ = tx.send(Action::JournalApplied(direction, entry, result));
+            let _ = tx.send(Action::Refresh);
+        });
+        None
+    }
+
+    fn on_journal_applied(
+        &mut self,
+        direction: Replay,
+        entry: JournalEntry,
+        result: Result<(), String>,
+    ) -> Option<Action> {
+        self.replaying = false;
+        match result {
+            Ok(()) => {
+                self.journal.push(direction.opposite(), entry);
+                None
+            },
+            Err(e) => {
+                let error = format!("Can't {} \"{}\": {e}", direction, entry.description);
+                self.journal.push(direction, entry);
+                Some(Action::Error(error))
+            },
+        }
+    }
+
+    fn on_listing_done(&mut self, id: usize) {
+        if self.cwd.as_ref().unwrap().listing != Some(id) {
+            return;
+        }
+        self.settle_children();
+        let cwd = self.cwd.as_mut().unwrap();
+        cwd.listing = None;
+        if !cwd.marked.is_empty() {
+            // Forget about marked entries that are gone.
+            let children = cwd.children.iter().map(|child| &child.path).collect::<HashSet<_>>();
+            cwd.marked.retain(|path| children.contains(path));
+        }
+        self.pending_selection = None;
     }
 }
 
@@ -66,49 +687,41 @@ fn get_dir_entry_icon(dir_entry_text: &str) -> String {
 
 impl Component for Home {
     fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.lister = Lister::new(tx.clone());
+        self.opener = Opener::new(tx.clone());
+        self.runner = CommandRunner::new(tx.clone());
         self.command_tx = Some(tx);
         Ok(())
     }
 
     fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.show_hidden = config.show_hidden;
+        self.show_preview = config.preview;
+        self.opener.set_openers(config.openers.clone());
         self.config = config;
         Ok(())
     }
 
+    fn init(&mut self, area: Rect) -> Result<()> {
+        self.journal = Journal::load(utils::get_data_dir().join("journal.json"));
+        self.refresh_listing();
+        self.announce_cwd();
+        Ok(())
+    }
+
     fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.mode != Mode::Filter || key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return Ok(None);
+        }
         match key.code {
-            KeyCode::Char('j') | KeyCode::Down => {
-                if self.curr_index.is_some() {
-                    self.curr_index.replace(self.curr_index.unwrap() + 1);
-                }
-            },
-            KeyCode::Char('k') | KeyCode::Up => {
-                if self.curr_index.is_some() && Some(0) != self.curr_index {
-                    self.curr_index.replace(self.curr_index.unwrap() - 1);
-                }
-            },
-            KeyCode::Enter => {
-                self.selected = true;
-            },
-            KeyCode::Left => {
-                if key.modifiers & KeyModifiers::ALT == KeyModifiers::ALT {
-                    if let Some(history_item) = self.history_backward.pop() {
-                        self.history_forward.push(self.cwd.take().unwrap());
-                        self.cwd = Some(history_item);
-                    }
-                }
-            },
-            KeyCode::Right => {
-                if key.modifiers & KeyModifiers::ALT == KeyModifiers::ALT {
-                    if let Some(history_item) = self.history_forward.pop() {
-                        self.history_backward.push(self.cwd.take().unwrap());
-                        self.cwd = Some(history_item);
-                    }
-                }
+            KeyCode::Char(c) => self.edit_filter(|filter| filter.pattern.push(c)),
+            KeyCode::Backspace => {
+                self.edit_filter(|filter| {
+                    filter.pattern.pop();
+                })
             },
             _ => {},
         }
-
         Ok(None)
     }
 
@@ -119,70 +732,407 @@ impl Component for Home {
                 //   self.cwd =
                 // }
             },
+            Action::ListingBatch(id, entries) => self.on_listing_batch(id, &entries),
+            Action::ListingDone(id) => self.on_listing_done(id),
+            // Actions may have moved the cursor since the last frame, and drawing it settled where.
+            Action::Render => self.announce_selection(),
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::Reveal(path) => self.reveal(path),
+            Action::TogglePreview => self.show_preview = !self.show_preview,
+            Action::Refresh => {
+                if self.pending_selection.is_none() {
+                    self.pending_selection = self.selected_entry().map(|entry| entry.path.clone());
+                }
+                self.refresh_listing();
+            },
+            Action::Error(message) => self.error = Some((message, Instant::now())),
+            Action::JobStarted(id, _) => {
+                self.jobs.insert(id);
+            },
+            Action::JobProgress(id, _) => {
+                self.failed_jobs.remove(&id);
+            },
+            Action::JobFailed(id, ..) => {
+                self.failed_jobs.insert(id);
+            },
+            Action::JobDone(id) => {
+                self.jobs.remove(&id);
+                self.failed_jobs.remove(&id);
+            },
+            Action::FileOp(op) if !op.is_long_running() => {
+                if let Some(tx) = &self.command_tx {
+                    fileops::spawn(op, tx.clone());
+                }
+            },
+            Action::Recorded(entry) => {
+                self.forget_moved(&entry);
+                self.journal.record(entry);
+            },
+            Action::JournalApplied(direction, entry, result) => {
+                return Ok(self.on_journal_applied(direction, entry, result));
+            },
+            Action::PromptSubmitted(purpose, input) => return Ok(self.on_prompt_submitted(purpose, input)),
+            // Everything below only concerns the listing while it has the focus.
+            _ if !matches!(self.mode, Mode::Home | Mode::Filter | Mode::Visual) => {},
             Action::Help => {},
+            Action::ToggleHidden => {
+                self.show_hidden = !self.show_hidden;
+                let index = self.curr_index;
+                self.apply_filter();
+                // The selected entry may have just been hidden, in which case the cursor stays where it was.
+                if self.curr_index.is_none() && !self.visible.is_empty() {
+                    self.curr_index = Some(index.unwrap_or_default().min(self.visible.len() - 1));
+                }
+            },
+            Action::GoToParent => self.go_to_parent(),
+            Action::Open => self.open_selected(),
+            Action::OpenWith => self.opener.open_with(self.operands()),
+            Action::RunCommand(name) => return Ok(self.run_command(&name)),
+            Action::EnterCommandLine => {
+                let names =
+                    self.markable().map(
+                        |entry| {
+                            if entry.is_dir {
+                                format!("{}/", entry.name)
+                            } else {
+                                entry.name.clone()
+                            }
+                        },
+                    );
+                return Ok(Some(Action::OpenCommandLine(names.collect())));
+            },
+            Action::HistoryBack => self.history_back(),
+            Action::HistoryForward => self.history_forward(),
+            Action::SelectNext => self.move_cursor(1),
+            Action::SelectPrevious => self.move_cursor(-1),
+            Action::PageDown => self.move_cursor(self.viewport_height as isize),
+            Action::PageUp => self.move_cursor(-(self.viewport_height as isize)),
+            Action::HalfPageDown => self.move_cursor((self.viewport_height / 2).max(1) as isize),
+            Action::HalfPageUp => self.move_cursor(-((self.viewport_height / 2).max(1) as isize)),
+            Action::SelectFirst => self.curr_index = Some(0),
+            Action::SelectLast => self.curr_index = self.visible.len().checked_sub(1),
+            Action::CycleSort => self.update_sort_spec(|sort_spec| sort_spec.key = sort_spec.key.next()),
+            Action::ReverseSort => self.update_sort_spec(|sort_spec| sort_spec.descending = !sort_spec.descending),
+            Action::ToggleDirsFirst => self.update_sort_spec(|sort_spec| sort_spec.dirs_first = !sort_spec.dirs_first),
+            Action::ToggleSortCase => {
+                self.update_sort_spec(|sort_spec| sort_spec.case_insensitive = !sort_spec.case_insensitive)
+            },
+            Action::EnterFilter => {
+                self.filter.get_or_insert_with(Filter::default);
+                return Ok(Some(Action::SwitchMode(Mode::Filter)));
+            },
+            Action::AcceptFilter => {
+                if self.filter.as_ref().is_some_and(|filter| filter.pattern.is_empty()) {
+                    self.filter = None;
+                }
+                return Ok(Some(Action::SwitchMode(Mode::Home)));
+            },
+            Action::ClearFilter => {
+                self.filter = None;
+                self.apply_filter();
+                if self.mode == Mode::Filter {
+                    return Ok(Some(Action::SwitchMode(Mode::Home)));
+                }
+            },
+            Action::CycleFilterKind if self.mode == Mode::Filter => {
+                self.edit_filter(|filter| filter.kind = filter.kind.next())
+            },
+            Action::Copy
+            | Action::Move
+            | Action::Rename
+            | Action::BulkRename
+            | Action::PatternRename
+            | Action::Trash
+            | Action::Delete
+            | Action::CreateDir
+            | Action::CreateFile
+                if self.mode == Mode::Home =>
+            {
+                return Ok(self.prepare_file_op(action));
+            },
+            Action::Copy
+            | Action::Move
+            | Action::BulkRename
+            | Action::PatternRename
+            | Action::Trash
+            | Action::Delete
+                if self.mode == Mode::Visual =>
+            {
+                self.accept_visual();
+                if let Some(tx) = &self.command_tx {
+                    let _ = tx.send(Action::SwitchMode(Mode::Home));
+                }
+                return Ok(self.prepare_file_op(action));
+            },
+            Action::Yank | Action::Cut if self.mode == Mode::Visual => {
+                self.accept_visual();
+                self.fill_register(action == Action::Cut);
+                return Ok(Some(Action::SwitchMode(Mode::Home)));
+            },
+            Action::Yank if self.mode == Mode::Home => self.fill_register(false),
+            Action::Cut if self.mode == Mode::Home => self.fill_register(true),
+            Action::Paste if self.mode == Mode::Home => return Ok(self.paste()),
+            Action::ClearRegister if self.mode == Mode::Home => self.register = None,
+            Action::Undo if self.mode == Mode::Home => return Ok(self.replay(Replay::Undo)),
+            Action::Redo if self.mode == Mode::Home => return Ok(self.replay(Replay::Redo)),
+            Action::ToggleMark if self.mode == Mode::Home => self.toggle_mark(),
+            Action::MarkAll if self.mode == Mode::Home => self.mark_all(),
+            Action::InvertMarks if self.mode == Mode::Home => self.invert_marks(),
+            Action::ClearMarks if self.mode == Mode::Home => self.cwd.as_mut().unwrap().marked.clear(),
+            Action::EnterVisual if self.mode == Mode::Home => {
+                self.visual_anchor = self.selected_entry().map(|entry| entry.path.clone());
+                if self.visual_anchor.is_some() {
+                    return Ok(Some(Action::SwitchMode(Mode::Visual)));
+                }
+            },
+            Action::AcceptVisual if self.mode == Mode::Visual => {
+                self.accept_visual();
+                return Ok(Some(Action::SwitchMode(Mode::Home)));
+            },
+            Action::CancelVisual if self.mode == Mode::Visual => {
+                self.visual_anchor = None;
+                return Ok(Some(Action::SwitchMode(Mode::Home)));
+            },
             _ => {},
         }
         Ok(None)
     }
 
     fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
-        if self.selected && self.curr_index.is_some() {
-            let selected_item =
-                self.cwd.as_ref().unwrap().children[*self.curr_index.as_ref().unwrap()].as_ref().unwrap().path.clone();
-            if Path::new(&selected_item).is_dir() {
-                let cwd = self.cwd.take().unwrap();
-                self.cwd = Some(WorkingDirectory { path: selected_item, children: vec![] });
-                self.history_backward.push(cwd);
-            }
-            self.selected = false;
-        }
-
-        let cwd = self.cwd.as_mut().unwrap();
-
-        if cwd.children.is_empty() {
-            cwd.children.push(Some(DirEntry { path: "..".to_owned(), ..DirEntry::default() }));
-            if let Ok(res) = Path::new(&cwd.path).read_dir() {
-                for entry in res.flatten() {
-                    let path = entry.path().to_str().unwrap().to_string();
-                    cwd.children.push(Some(DirEntry {
-                        is_dir: Path::new(&path).is_dir(),
-                        path,
-                        ..DirEntry::default()
-                    }));
-                }
-            }
+        self.settle_children();
+        if self.error.as_ref().is_some_and(|(_, at)| at.elapsed() > ERROR_TIMEOUT) {
+            self.error = None;
         }
 
+        let len = self.visible.len();
         if self.curr_index.is_none() {
-            if !cwd.children.is_empty() {
+            if len > 0 {
                 self.curr_index = Some(0);
             }
-        } else {
-            self.curr_index = Some(min(*self.curr_index.as_ref().unwrap(), cwd.children.len() - 1));
+        } else if let Some(curr_index) = self.curr_index {
+            self.curr_index = len.checked_sub(1).map(|last| min(curr_index, last));
         }
 
-        let lines = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(cwd.children.iter().map(|_| Constraint::Length(1)).collect::<Vec<Constraint>>())
-            .split(area);
+        let MainLayout { title: title_area, listing: area, status: status_area, .. } =
+            main_layout(area, self.config.layout, self.show_preview);
 
-        for i in 0..cwd.children.len() {
-            let dir_entry = cwd.children[i].as_ref().unwrap();
-            let mut dir_entry_text = dir_entry.path.clone();
-            if dir_entry.is_dir {
-                dir_entry_text = "📂".to_owned() + &dir_entry_text;
+        // One row goes to the table header.
+        self.viewport_height = area.height.saturating_sub(1) as usize;
+        if let Some(curr_index) = self.curr_index {
+            self.scroll_to(curr_index);
+        }
+
+        let columns = column::fit(&self.config.columns, area.width);
+        let widths = columns.iter().map(|column| {
+            match column {
+                Column::Name => Constraint::Fill(2),
+                column => column.width().map_or(Constraint::Fill(1), Constraint::Length),
+            }
+        });
+        let sort_spec = self.sort_spec();
+        let sorted_column = match sort_spec.key {
+            SortKey::Name => Some(Column::Name),
+            SortKey::Size => Some(Column::Size),
+            SortKey::Modified => Some(Column::Modified),
+            SortKey::Type => Some(Column::Type),
+            SortKey::Extension => None,
+        };
+        let header = Row::new(columns.iter().map(|column| {
+            if Some(*column) == sorted_column {
+                format!("{} {}", column.title(), if sort_spec.descending { '▼' } else { '▲' })
             } else {
-                dir_entry_text = get_dir_entry_icon(&dir_entry_text) + &dir_entry_text;
+                column.title().to_owned()
             }
+        }))
+        .bold();
 
-            let mut paragraph = Paragraph::new(dir_entry_text.as_str());
-            let is_selected = self.curr_index.is_some() && Some(i) == self.curr_index;
-            if is_selected {
-                paragraph = paragraph.set_style(Style::new().bg(Color::Magenta));
-            }
-            f.render_widget(paragraph, lines[i]);
+        let cwd = self.cwd.as_ref().unwrap();
+        let title = Block::default()
+            .title(cwd.path.display().to_string().bold())
+            .title(block::Title::from(format!("sorted by {sort_spec}").dim()).alignment(Alignment::Right));
+        f.render_widget(title, title_area);
+
+        let matcher = self.filter.as_ref().and_then(|filter| filter.matcher.as_ref());
+        let visual_range = self.visual_range().filter(|_| self.mode == Mode::Visual);
+        let visible =
+            self.visible.iter().map(|&i| &cwd.children[i]).enumerate().skip(self.offset).take(self.viewport_height);
+        let mut rows = visible
+            .map(|(i, dir_entry)| {
+                let is_marked = dir_entry.name != ".."
+                    && (cwd.marked.contains(&dir_entry.path)
+                        || visual_range.as_ref().is_some_and(|range| range.contains(&i)));
+                let row = Row::new(columns.iter().map(|column| {
+                    match column {
+                        Column::Name => {
+                            let icon =
+                                if dir_entry.is_dir { "📂".to_owned() } else { get_dir_entry_icon(&dir_entry.name) };
+                            let highlighted =
+                                matcher.and_then(|matcher| matcher.matches(&dir_entry.name)).unwrap_or_default();
+                            let mark = if is_marked { "▌".yellow() } else { Span::raw(" ") };
+                            let mut spans = vec![mark, Span::raw(icon)];
+                            spans.extend(dir_entry.name.chars().enumerate().map(|(i, c)| {
+                                if highlighted.indices.contains(&i) {
+                                    Span::styled(c.to_string(), Style::new().fg(Color::Yellow).bold())
+                                } else {
+                                    Span::raw(c.to_string())
+                                }
+                            }));
+                            Cell::from(Line::from(spans))
+                        },
+                        column => Cell::from(column.cell(dir_entry)),
+                    }
+                }));
+                let is_selected = self.curr_index.is_some() && Some(i) == self.curr_index;
+                match (is_selected, is_marked) {
+                    (true, _) => row.style(Style::new().bg(Color::Magenta)),
+                    (false, true) => row.style(Style::new().fg(Color::Yellow)),
+                    (false, false) => row,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if cwd.is_loading() && rows.len() < self.viewport_height {
+            rows.push(Row::new(["loading…".dim()]));
         }
 
+        let table = Table::new(rows, widths).header(header).column_spacing(column::COLUMN_SPACING);
+        f.render_widget(table, area);
+
+        let status = match &self.filter {
+            Some(filter) => {
+                let cursor = if self.mode == Mode::Filter { "▏" } else { "" };
+                let summary = match &filter.error {
+                    Some(error) => error.clone().red(),
+                    None => format!("[{}] {} matches", filter.kind, self.visible.len()).dim(),
+                };
+                Block::default()
+                    .title(format!("/{}{cursor}", filter.pattern))
+                    .title(block::Title::from(summary).alignment(Alignment::Right))
+            },
+            None => {
+                let marked = match (self.mode, self.visual_range(), cwd.marked.len()) {
+                    (Mode::Visual, Some(range), _) => format!(" · -- VISUAL -- {} selected", range.count()),
+                    (_, _, 0) => String::new(),
+                    (_, _, n) => format!(" · {n} marked"),
+                };
+                let register = match &self.register {
+                    None => String::new(),
+                    Some(Register { paths, cut }) => {
+                        let verb = if *cut { "cut" } else { "yanked" };
+                        match paths.as_slice() {
+                            [path] => format!(" · {verb} {}", fileops::display_name(path)),
+                            paths => format!(" · {} {verb}", paths.len()),
+                        }
+                    },
+                };
+                let status = match &self.error {
+                    Some((error, _)) => Block::default().title(error.clone().red()),
+                    None => Block::default().title(format!("{} entries{marked}{register}", cwd.children.len()).dim()),
+                };
+                let jobs = match (self.jobs.len(), self.failed_jobs.len()) {
+                    (0, _) => "".into(),
+                    (_, 1..) => "job needs attention".red(),
+                    (1, _) => "1 job running".dim(),
+                    (n, _) => format!("{n} jobs running").dim(),
+                };
+                status.title(block::Title::from(jobs).alignment(Alignment::Right))
+            },
+        };
+        f.render_widget(status, status_area);
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// A listing of the temporary directory made of `names`, none of which needs to exist.
+    fn listing(names: &[&str]) -> Home {
+        let mut home = Home::new(Some(std::env::temp_dir())).unwrap();
+        let cwd = home.cwd.as_ref().unwrap().path.clone();
+        home.cwd.as_mut().unwrap().children = names
+            .iter()
+            .map(|&name| DirEntry { path: cwd.join(name), name: name.to_owned(), ..DirEntry::default() })
+            .collect();
+        home
+    }
+
+    #[test]
+    fn test_toggle_hidden() {
+        let mut home = listing(&["..", ".git", "a.rs", ".zshrc", "b.rs"]);
+        home.show_hidden = true;
+        home.update_visible();
+        home.curr_index = Some(3);
+        let visible = |home: &Home| {
+            let children = &home.cwd.as_ref().unwrap().children;
+            home.visible.iter().map(|&i| children[i].name.clone()).collect::<Vec<_>>()
+        };
+
+        home.update(Action::ToggleHidden).unwrap();
+        assert_eq!(visible(&home), vec!["..", "a.rs", "b.rs"]);
+        // `.zshrc` went away from under the cursor, which stays on the last entry.
+        assert_eq!(home.selected_entry().map(|entry| entry.name.as_str()), Some("b.rs"));
+
+        home.update(Action::ToggleHidden).unwrap();
+        assert_eq!(visible(&home), vec!["..", ".git", "a.rs", ".zshrc", "b.rs"]);
+        assert_eq!(home.selected_entry().map(|entry| entry.name.as_str()), Some("b.rs"));
+    }
+
+    #[test]
+    fn test_select_last() {
+        let mut home = listing(&["..", "a.rs", "b.rs"]);
+        home.update_visible();
+        home.update(Action::SelectLast).unwrap();
+        assert_eq!(home.curr_index, Some(2));
+
+        let mut home = listing(&[]);
+        home.update_visible();
+        home.update(Action::SelectLast).unwrap();
+        assert_eq!(home.curr_index, None);
+    }
+
+    #[test]
+    fn test_rename_marked() {
+        let mut home = listing(&["..", "a.rs", "b.rs", "c.rs"]);
+        home.update_visible();
+        home.curr_index = Some(1);
+        let cwd = home.cwd.as_ref().unwrap().path.clone();
+        assert!(matches!(
+            home.prepare_file_op(Action::Rename),
+            Some(Action::Prompt(PromptPurpose::Rename(path), name)) if path == cwd.join("a.rs") && name == "a.rs"
+        ));
+
+        home.cwd.as_mut().unwrap().marked.extend([cwd.join("c.rs"), cwd.join("a.rs")]);
+        assert!(matches!(
+            home.prepare_file_op(Action::Rename),
+            Some(Action::OpenPatternRename(paths)) if paths == [cwd.join("a.rs"), cwd.join("c.rs")]
+        ));
+    }
+
+    #[test]
+    fn test_cut_register() {
+        let mut home = listing(&["..", "a.rs", "b.rs"]);
+        let cwd = home.cwd.as_ref().unwrap().path.clone();
+        home.cwd.as_mut().unwrap().marked.extend([cwd.join("a.rs"), cwd.join("b.rs")]);
+        home.fill_register(true);
+        let moved = |name: &str| {
+            let change = Change::moved(&cwd.join(name), &cwd.join("dest").join(name));
+            Action::Recorded(JournalEntry { description: String::new(), changes: vec![change] })
+        };
+
+        // Nothing moved yet, as the move may still fail.
+        let paste = home.paste().unwrap();
+        assert!(matches!(paste, Action::FileOp(FileOp::Move { .. })));
+        home.update(paste).unwrap();
+        assert_eq!(home.register.as_ref().map(|register| register.paths.len()), Some(2));
+
+        home.update(moved("a.rs")).unwrap();
+        assert_eq!(home.register.as_ref().map(|register| register.paths.clone()), Some(vec![cwd.join("b.rs")]));
+        home.update(moved("b.rs")).unwrap();
+        assert!(home.register.is_none());
+    }
+}
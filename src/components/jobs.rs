@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{centered_rect, Component, Frame};
+use crate::{
+    action::Action,
+    column::human_size,
+    mode::Mode,
+    services::jobs::{FailureChoice, JobManager, JobProgress},
+};
+
+/// Rows each job takes in the panel.
+const JOB_HEIGHT: u16 = 3;
+
+struct Job {
+    id: usize,
+    description: String,
+    progress: JobProgress,
+    paused: bool,
+    /// File the job is stuck on and why, until the user picks what to do about it.
+    failure: Option<(PathBuf, String)>,
+}
+
+/// Panel listing the copy, move and delete jobs running in the background, where they can be paused, resumed and
+/// cancelled, and where failures are resolved. Pops up by itself when a job fails while the listing has the focus.
+#[derive(Default)]
+pub struct Jobs {
+    command_tx: Option<UnboundedSender<Action>>,
+    manager: JobManager,
+    mode: Mode,
+    jobs: Vec<Job>,
+    curr_index: usize,
+    offset: usize,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn job_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        self.curr_index = self.curr_index.saturating_add_signed(delta).min(self.jobs.len().saturating_sub(1));
+    }
+
+    fn on_failed(&mut self, id: usize, path: PathBuf, error: String) -> Option<Action> {
+        let position = self.jobs.iter().position(|job| job.id == id)?;
+        self.jobs[position].failure = Some((path, error));
+        if self.mode != Mode::Home {
+            return None;
+        }
+        self.curr_index = position;
+        Some(Action::SwitchMode(Mode::Jobs))
+    }
+
+    fn on_done(&mut self, id: usize) -> Option<Action> {
+        self.manager.remove(id);
+        self.jobs.retain(|job| job.id != id);
+        self.move_cursor(0);
+        (self.jobs.is_empty() && self.mode == Mode::Jobs).then_some(Action::SwitchMode(Mode::Home))
+    }
+
+    fn resolve(&mut self, choice: FailureChoice) {
+        let Some(job) = self.jobs.get_mut(self.curr_index) else {
+            return;
+        };
+        if job.failure.take().is_some() {
+            self.manager.resolve(job.id, choice);
+        }
+    }
+
+    fn job_lines(job: &Job) -> (Line<'static>, String, Line<'static>) {
+        let progress = &job.progress;
+        let state = match (&job.failure, job.paused) {
+            (Some(_), _) => " needs attention".red(),
+            (None, true) => " paused".yellow(),
+            (None, false) => "".into(),
+        };
+        let title = Line::from(vec![job.description.clone().bold(), state]);
+        let mut label = format!("{}/{} files", progress.files_done, progress.files_total);
+        if progress.bytes_total > 0 {
+            label +=
+                &format!(" · {} of {}", human_size(progress.bytes_done), human_size(progress.bytes_total).trim_start());
+            if !job.paused && job.failure.is_none() {
+                label += &format!(" · {}/s", human_size(progress.bytes_per_sec).trim_start());
+            }
+        }
+        let detail = match &job.failure {
+            Some((path, error)) => {
+                Line::from(vec![format!("{}: {error} ", path.display()).red(), "[r]etry [s]kip [x] abort".bold()])
+            },
+            None => {
+                Line::from(progress.current.as_ref().map(|path| path.display().to_string()).unwrap_or_default().dim())
+            },
+        };
+        (title, label, detail)
+    }
+}
+
+impl Component for Jobs {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.manager = JobManager::new(tx.clone());
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::Quit => self.manager.cancel_all(),
+            Action::FileOp(op) if op.is_long_running() => {
+                self.manager.start(op);
+            },
+            Action::JobStarted(id, description) => {
+                self.jobs.push(Job { id, description, progress: JobProgress::default(), paused: false, failure: None })
+            },
+            Action::JobProgress(id, progress) => {
+                if let Some(job) = self.job_mut(id) {
+                    job.progress = progress;
+                }
+            },
+            Action::JobFailed(id, path, error) => return Ok(self.on_failed(id, path, error)),
+            Action::JobDone(id) => return Ok(self.on_done(id)),
+            Action::OpenJobs if self.mode == Mode::Home => return Ok(Some(Action::SwitchMode(Mode::Jobs))),
+            _ if self.mode != Mode::Jobs => {},
+            Action::CloseJobs => return Ok(Some(Action::SwitchMode(Mode::Home))),
+            Action::SelectNext => self.move_cursor(1),
+            Action::SelectPrevious => self.move_cursor(-1),
+            Action::ToggleJobPause => {
+                let Some(job) = self.jobs.get_mut(self.curr_index) else {
+                    return Ok(None);
+                };
+                job.paused = !job.paused;
+                self.manager.set_paused(job.id, job.paused);
+            },
+            Action::CancelJob => {
+                if let Some(job) = self.jobs.get(self.curr_index) {
+                    self.manager.cancel(job.id);
+                }
+            },
+            Action::RetryFailedFile => self.resolve(FailureChoice::Retry),
+            Action::SkipFailedFile => self.resolve(FailureChoice::Skip),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::Jobs {
+            return Ok(());
+        }
+
+        let area = centered_rect(80, 60, area);
+        f.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title(format!("Jobs ({})", self.jobs.len()));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        if self.jobs.is_empty() {
+            f.render_widget(Paragraph::new("No jobs running".dim()), inner);
+            return Ok(());
+        }
+
+        let fitting = (inner.height / JOB_HEIGHT).max(1) as usize;
+        if self.curr_index < self.offset {
+            self.offset = self.curr_index;
+        } else if self.curr_index >= self.offset + fitting {
+            self.offset = self.curr_index + 1 - fitting;
+        }
+        for (row, (i, job)) in self.jobs.iter().enumerate().skip(self.offset).take(fitting).enumerate() {
+            let job_area =
+                Rect { y: inner.y + row as u16 * JOB_HEIGHT, height: JOB_HEIGHT, ..inner }.intersection(inner);
+            let [title_area, gauge_area, detail_area] = *Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1); 3])
+                .split(job_area)
+            else {
+                unreachable!()
+            };
+            let (title, label, detail) = Self::job_lines(job);
+            let title = if i == self.curr_index { title.style(Style::new().bg(Color::Magenta)) } else { title };
+            let progress = &job.progress;
+            let ratio = if progress.bytes_total > 0 {
+                progress.bytes_done as f64 / progress.bytes_total as f64
+            } else if progress.files_total > 0 {
+                progress.files_done as f64 / progress.files_total as f64
+            } else {
+                0.0
+            };
+            let gauge = LineGauge::default()
+                .ratio(ratio.clamp(0.0, 1.0))
+                .label(label)
+                .gauge_style(Style::new().fg(if job.paused { Color::Yellow } else { Color::Green }));
+            f.render_widget(Paragraph::new(title), title_area);
+            f.render_widget(gauge, gauge_area);
+            f.render_widget(Paragraph::new(detail), detail_area);
+        }
+
+        Ok(())
+    }
+}
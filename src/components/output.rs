@@ -0,0 +1,144 @@
+use color_eyre::eyre::Result;
+use ratatui::{prelude::*, widgets::*};
+
+use super::{centered_rect, Component, Frame};
+use crate::{action::Action, mode::Mode};
+
+/// Lines kept from the output of a streamed command, the oldest being dropped first.
+const MAX_LINES: usize = 10_000;
+
+/// Popup showing what a command printed, scrolled through like a pager. The output of a command from the config is
+/// shown once it exits, while that of a command typed on the command line streams in as it runs, with the view
+/// following it until scrolled up.
+#[derive(Default)]
+pub struct Output {
+    mode: Mode,
+    title: String,
+    lines: Vec<String>,
+    /// Index of the first line shown.
+    scroll: usize,
+    viewport_height: usize,
+    /// Whether the view sticks to the last line as more come in.
+    follow: bool,
+    /// Id of the streamed command shown, and how it exited once it has.
+    stream: Option<usize>,
+    result: Option<Result<(), String>>,
+}
+
+impl Output {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn show(&mut self, title: String, stream: Option<usize>) -> Option<Action> {
+        self.title = title;
+        self.lines.clear();
+        self.scroll = 0;
+        self.follow = stream.is_some();
+        self.stream = stream;
+        self.result = None;
+        Some(Action::SwitchMode(Mode::Output))
+    }
+
+    fn push_lines<'a>(&mut self, lines: impl IntoIterator<Item = &'a str>) {
+        // Colors and other control characters would throw the drawing off.
+        self.lines.extend(lines.into_iter().map(|line| {
+            strip_ansi_escapes::strip_str(line.replace('\t', "    ")).chars().filter(|c| !c.is_control()).collect()
+        }));
+        let excess = self.lines.len().saturating_sub(MAX_LINES);
+        self.lines.drain(..excess);
+        self.scroll = self.scroll.saturating_sub(excess);
+        if self.follow {
+            self.scroll_by(isize::MAX);
+        }
+    }
+
+    fn last_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(self.viewport_height.max(1))
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        self.scroll = self.scroll.saturating_add_signed(delta).min(self.last_scroll());
+    }
+
+    /// Scrolls through the output, following it again once back at the end.
+    fn scroll_to(&mut self, delta: isize) {
+        self.scroll_by(delta);
+        self.follow = self.stream.is_some() && self.scroll == self.last_scroll();
+    }
+}
+
+impl Component for Output {
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::ShowOutput(title, text) => {
+                let action = self.show(title, None);
+                self.push_lines(text.lines());
+                return Ok(action);
+            },
+            Action::OutputStarted(id, command) => return Ok(self.show(command, Some(id))),
+            Action::OutputLines(id, lines) if self.stream == Some(id) => {
+                self.push_lines(lines.iter().map(String::as_str))
+            },
+            Action::OutputDone(id, result) if self.stream == Some(id) => self.result = Some(result),
+            _ if self.mode != Mode::Output => {},
+            Action::CloseOutput => return Ok(Some(Action::SwitchMode(Mode::Home))),
+            Action::SelectNext => self.scroll_to(1),
+            Action::SelectPrevious => self.scroll_to(-1),
+            Action::PageDown => self.scroll_to(self.viewport_height as isize),
+            Action::PageUp => self.scroll_to(-(self.viewport_height as isize)),
+            Action::SelectFirst => self.scroll_to(isize::MIN),
+            Action::SelectLast => self.scroll_to(isize::MAX),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::Output {
+            return Ok(());
+        }
+
+        let area = centered_rect(90, 80, area);
+        f.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title(self.title.as_str());
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let [text_area, status_area] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(inner)
+        else {
+            unreachable!()
+        };
+
+        self.viewport_height = text_area.height as usize;
+        // The popup may have been resized since the last scroll.
+        self.scroll_by(if self.follow { isize::MAX } else { 0 });
+        let lines = self.lines.iter().skip(self.scroll).take(self.viewport_height).map(|line| Line::raw(line.as_str()));
+        f.render_widget(Paragraph::new(lines.collect::<Vec<_>>()), text_area);
+
+        let summary = match self.lines.len() {
+            0 => "No output".to_owned(),
+            1 => "1 line".to_owned(),
+            len if len <= self.viewport_height => format!("{len} lines"),
+            len => {
+                let last = (self.scroll + self.viewport_height).min(len);
+                format!("Lines {}-{last} of {len}", self.scroll + 1)
+            },
+        };
+        let result = match (self.stream, &self.result) {
+            (None, _) => "".into(),
+            (Some(_), None) => "running… (Ctrl-c to stop)".dim(),
+            (Some(_), Some(Ok(()))) => "done".dim(),
+            (Some(_), Some(Err(e))) => e.clone().red(),
+        };
+        let status =
+            Block::default().title(summary.dim()).title(block::Title::from(result).alignment(Alignment::Right));
+        f.render_widget(status, status_area);
+
+        Ok(())
+    }
+}
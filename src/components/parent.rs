@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{main_layout, preview::directory_lines, Component, Frame, LayoutMode};
+use crate::{
+    action::Action,
+    config::Config,
+    services::{
+        fileops::display_name,
+        preview::{Preview, PreviewContent, Previewer},
+    },
+};
+
+/// Left pane of the Miller layout, listing the parent of the working directory with the working directory selected.
+#[derive(Default)]
+pub struct Parent {
+    config: Config,
+    previewer: Previewer,
+    cwd: Option<PathBuf>,
+    listing: Option<Preview>,
+    show_preview: bool,
+}
+
+impl Parent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn parent(&self) -> Option<PathBuf> {
+        self.cwd.as_deref()?.parent().map(PathBuf::from)
+    }
+
+    fn load(&mut self) {
+        if self.config.layout != LayoutMode::Miller {
+            return;
+        }
+        match self.parent() {
+            Some(parent) => self.previewer.load(&parent, None),
+            None => {
+                self.previewer.cancel();
+                self.listing = None;
+            },
+        }
+    }
+}
+
+impl Component for Parent {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.previewer = Previewer::new(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.show_preview = config.preview;
+        self.config = config;
+        Ok(())
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::DirectoryChanged(path) => {
+                self.cwd = Some(path);
+                self.load();
+            },
+            Action::PreviewLoaded(preview) if Some(&preview.path) == self.parent().as_ref() => {
+                self.listing = Some(preview)
+            },
+            // Siblings of the working directory may have come and gone.
+            Action::Refresh => self.load(),
+            Action::ToggleHidden => self.config.show_hidden = !self.config.show_hidden,
+            Action::TogglePreview => self.show_preview = !self.show_preview,
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let Some(area) = main_layout(area, self.config.layout, self.show_preview).parent else {
+            return Ok(());
+        };
+        let block = Block::default().borders(Borders::RIGHT);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let cwd_name = self.cwd.as_deref().map(display_name);
+        let lines = match self.listing.as_ref().map(|listing| &listing.content) {
+            Some(PreviewContent::Directory(entries)) => {
+                // Keep the working directory in view, in the middle when there are more entries than fit.
+                let height = inner.height as usize;
+                let position = entries
+                    .iter()
+                    .filter(|(name, _)| self.config.show_hidden || !name.starts_with('.'))
+                    .position(|(name, _)| Some(name) == cwd_name.as_ref())
+                    .unwrap_or_default();
+                let scroll = position.saturating_sub(height / 2);
+                directory_lines(entries, self.config.show_hidden, scroll, height, cwd_name.as_deref())
+            },
+            Some(PreviewContent::Error(error)) => vec![Line::from(error.clone().red())],
+            _ => vec![],
+        };
+        f.render_widget(Paragraph::new(lines), inner);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{
+    bulk_rename::{rename_row, rename_status, RENAME_TABLE_WIDTHS},
+    centered_rect, Component, Frame,
+};
+use crate::{
+    action::Action,
+    mode::Mode,
+    rename::{RenamePattern, Renamer},
+    services::fileops::{display_name, rename_conflicts, FileOp},
+};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+enum Field {
+    #[default]
+    Find,
+    Replace,
+    Extension,
+}
+
+impl Field {
+    const ALL: [Field; 3] = [Field::Find, Field::Replace, Field::Extension];
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Find => "Find",
+            Field::Replace => "Replace",
+            Field::Extension => "Extension",
+        }
+    }
+}
+
+/// Dialog renaming entries by regex find and replace, numbering, case changes and a new extension, previewing the new
+/// names as the pattern is typed.
+#[derive(Default)]
+pub struct PatternRename {
+    command_tx: Option<UnboundedSender<Action>>,
+    mode: Mode,
+    /// Entries to rename in order, which is what `{n}` counts, and whether they are directories.
+    entries: Vec<(PathBuf, bool)>,
+    pattern: RenamePattern,
+    focus: Field,
+    error: Option<String>,
+    /// Old and new names of the entries whose name changes.
+    renames: Vec<(PathBuf, String)>,
+    conflicts: Vec<Option<&'static str>>,
+    curr_index: usize,
+    offset: usize,
+    viewport_height: usize,
+}
+
+impl PatternRename {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn open(&mut self, paths: Vec<PathBuf>) -> Option<Action> {
+        self.entries = paths.into_iter().map(|path| (path.clone(), path.is_dir())).collect();
+        self.pattern = RenamePattern::default();
+        self.focus = Field::default();
+        self.update_preview();
+        Some(Action::SwitchMode(Mode::PatternRename))
+    }
+
+    fn field_mut(&mut self) -> &mut String {
+        match self.focus {
+            Field::Find => &mut self.pattern.find,
+            Field::Replace => &mut self.pattern.replace,
+            Field::Extension => &mut self.pattern.extension,
+        }
+    }
+
+    fn update_preview(&mut self) {
+        self.renames.clear();
+        self.conflicts.clear();
+        self.curr_index = 0;
+        self.offset = 0;
+        let renamer = match Renamer::new(&self.pattern) {
+            Ok(renamer) => renamer,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            },
+        };
+        self.error = None;
+        let mut invalid = vec![];
+        for (n, (path, is_dir)) in self.entries.iter().enumerate() {
+            let name = display_name(path);
+            let new_name = renamer.rename(&name, *is_dir, n + 1);
+            if new_name != name {
+                invalid.push(
+                    new_name.is_empty()
+                        || new_name == "."
+                        || new_name == ".."
+                        || new_name.contains(std::path::is_separator),
+                );
+                self.renames.push((path.clone(), new_name));
+            }
+        }
+        self.conflicts = rename_conflicts(&self.targets());
+        for (conflict, invalid) in self.conflicts.iter_mut().zip(invalid) {
+            if invalid {
+                *conflict = Some("invalid name");
+            }
+        }
+    }
+
+    fn targets(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.renames.iter().map(|(from, name)| (from.clone(), from.with_file_name(name))).collect()
+    }
+
+    fn apply(&mut self) -> Option<Action> {
+        if self.error.is_some() || self.conflicts.iter().any(Option::is_some) {
+            return None;
+        }
+        let renames = self.targets();
+        self.entries.clear();
+        self.renames.clear();
+        if renames.is_empty() {
+            return Some(Action::SwitchMode(Mode::Home));
+        }
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.send(Action::SwitchMode(Mode::Home));
+        }
+        Some(Action::FileOp(FileOp::RenameAll { renames }))
+    }
+
+    fn cycle_focus(&mut self, delta: isize) {
+        let index = Field::ALL.iter().position(|&field| field == self.focus).unwrap_or(0);
+        self.focus = Field::ALL[(index as isize + delta).rem_euclid(Field::ALL.len() as isize) as usize];
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        self.curr_index = self.curr_index.saturating_add_signed(delta).min(self.renames.len().saturating_sub(1));
+    }
+
+    fn field_line(&self, field: Field) -> Line<'static> {
+        let value = match field {
+            Field::Find => &self.pattern.find,
+            Field::Replace => &self.pattern.replace,
+            Field::Extension => &self.pattern.extension,
+        };
+        let label = format!("{:<10}", field.label());
+        if field == self.focus {
+            Line::from(vec![label.bold(), format!("{value}▏").into()])
+        } else {
+            Line::from(vec![label.dim(), value.clone().into()])
+        }
+    }
+}
+
+impl Component for PatternRename {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.mode != Mode::PatternRename || key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return Ok(None);
+        }
+        match key.code {
+            KeyCode::Char(c) => {
+                self.field_mut().push(c);
+                self.update_preview();
+            },
+            KeyCode::Backspace => {
+                self.field_mut().pop();
+                self.update_preview();
+            },
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::OpenPatternRename(paths) => return Ok(self.open(paths)),
+            _ if self.mode != Mode::PatternRename => {},
+            Action::ApplyRenames => return Ok(self.apply()),
+            Action::CancelRenames => {
+                self.entries.clear();
+                self.renames.clear();
+                return Ok(Some(Action::SwitchMode(Mode::Home)));
+            },
+            Action::NextField => self.cycle_focus(1),
+            Action::PreviousField => self.cycle_focus(-1),
+            Action::CycleCase => {
+                self.pattern.case = self.pattern.case.next();
+                self.update_preview();
+            },
+            Action::SelectNext => self.move_cursor(1),
+            Action::SelectPrevious => self.move_cursor(-1),
+            Action::PageDown => self.move_cursor(self.viewport_height as isize),
+            Action::PageUp => self.move_cursor(-(self.viewport_height as isize)),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::PatternRename {
+            return Ok(());
+        }
+
+        let area = centered_rect(80, 80, area);
+        f.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title(format!("Rename {} entries", self.entries.len()));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let [fields_area, table_area, status_area] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(5), Constraint::Min(0), Constraint::Length(1)])
+            .split(inner)
+        else {
+            unreachable!()
+        };
+
+        let mut lines = Field::ALL.map(|field| self.field_line(field)).to_vec();
+        lines.push(Line::from(vec![format!("{:<10}", "Case").dim(), self.pattern.case.to_string().into()]));
+        lines.push(Line::from(
+            "$1 or ${name} for capture groups, {n} or {n:03} for numbering · [Tab] next field [Ctrl-t] case".dim(),
+        ));
+        f.render_widget(Paragraph::new(lines), fields_area);
+
+        self.viewport_height = table_area.height as usize;
+        let height = self.viewport_height.max(1);
+        if self.curr_index < self.offset {
+            self.offset = self.curr_index;
+        } else if self.curr_index >= self.offset + height {
+            self.offset = self.curr_index + 1 - height;
+        }
+        let rows = self.renames.iter().zip(&self.conflicts).enumerate().skip(self.offset).take(height).map(
+            |(i, ((from, name), conflict))| {
+                rename_row(display_name(from), name.clone(), *conflict, i == self.curr_index)
+            },
+        );
+        f.render_widget(Table::new(rows, RENAME_TABLE_WIDTHS), table_area);
+
+        let status = match &self.error {
+            Some(error) => Line::from(error.clone().red()),
+            None => rename_status(self.renames.len(), &self.conflicts),
+        };
+        f.render_widget(Paragraph::new(status), status_area);
+
+        Ok(())
+    }
+}
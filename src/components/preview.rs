@@ -0,0 +1,474 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Write,
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use color_eyre::eyre::Result;
+use crossterm::{
+    cursor::{MoveTo, RestorePosition, SavePosition},
+    style::Print,
+    QueueableCommand,
+};
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{main_layout, prompt::PromptPurpose, Component, Frame};
+use crate::{
+    action::Action,
+    column::human_size,
+    config::Config,
+    mode::Mode,
+    services::{
+        fileops::display_name,
+        image::{ImageProtocol, RenderedImage, KITTY_DELETE_ALL},
+        preview::{HexPage, ImagePreview, Preview as LoadedPreview, PreviewContent, Previewer},
+    },
+    tui,
+};
+
+/// Previews kept around, so that going back to an entry shows it right away.
+const MAX_CACHED: usize = 64;
+
+/// Pane to the right of the listing showing what is in the selected entry: the text of a file, highlighted, a picture
+/// of an image, a hex dump of a binary file, or the entries of a directory.
+#[derive(Default)]
+pub struct Preview {
+    config: Config,
+    previewer: Previewer,
+    shown: bool,
+    selected: Option<PathBuf>,
+    /// Previews by path, each replaced once the entry turns out to have been modified since it was loaded.
+    cache: HashMap<PathBuf, LoadedPreview>,
+    cached_order: VecDeque<PathBuf>,
+    loading: bool,
+    scroll: usize,
+    viewport_height: usize,
+    /// Offset of the first byte shown in the hex view, and of the byte jumped to if any.
+    hex_offset: u64,
+    hex_mark: Option<u64>,
+    /// How many bytes fit in a row of the hex view, as of the last time it was drawn.
+    bytes_per_row: u64,
+    /// Last page read from a binary file, for the parts of it past the start that comes with its preview.
+    hex_page: Option<HexPage>,
+    hex_requested: Option<u64>,
+    image_protocol: ImageProtocol,
+    /// The selected image, rendered for the size the pane had when it was requested.
+    image: Option<ImagePreview>,
+    image_requested: Option<(PathBuf, Option<SystemTime>, u16, u16)>,
+    /// Popups are drawn over the pane in other modes, which graphics would cover.
+    mode: Mode,
+    /// Image graphics drawn in this frame and those on the screen, by image and the cells they cover. They are written
+    /// straight to the terminal after the frame, as a cell holding them would throw off its diff with the last one.
+    graphics: Option<(PathBuf, Option<SystemTime>, Rect)>,
+    graphics_shown: Option<(PathBuf, Option<SystemTime>, Rect)>,
+    /// Count of the last frame. A fresh terminal, which starts out cleared, counts them over.
+    frame_count: usize,
+}
+
+impl Preview {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shows the preview of `path` from the cache, to be replaced if it turns out stale, or else loads it.
+    fn show(&mut self, path: Option<PathBuf>) {
+        if path != self.selected {
+            self.scroll = 0;
+            self.hex_offset = 0;
+            self.hex_mark = None;
+            self.hex_page = None;
+        }
+        self.selected = path;
+        // Whatever was in flight is requested again when drawn.
+        self.previewer.cancel();
+        self.hex_requested = None;
+        self.image_requested = None;
+        self.loading = false;
+        let Some(path) = self.selected.clone().filter(|_| self.shown) else {
+            return;
+        };
+        // A cached preview is shown while the previewer checks whether it is still fresh.
+        let cached = self.cache.get(&path).map(|preview| preview.modified);
+        self.loading = cached.is_none();
+        self.previewer.load(&path, cached);
+    }
+
+    fn on_loaded(&mut self, preview: LoadedPreview) {
+        if self.selected.as_ref() == Some(&preview.path) {
+            self.loading = false;
+        }
+        if self.cache.insert(preview.path.clone(), preview.clone()).is_none() {
+            self.cached_order.push_back(preview.path);
+            if self.cached_order.len() > MAX_CACHED {
+                if let Some(oldest) = self.cached_order.pop_front() {
+                    self.cache.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    fn current(&self) -> Option<&PreviewContent> {
+        self.selected.as_ref().and_then(|path| self.cache.get(path)).map(|preview| &preview.content)
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        let len = match self.current() {
+            Some(PreviewContent::Text { lines, .. }) => lines.len(),
+            Some(PreviewContent::Directory(entries)) => entries.len(),
+            Some(&PreviewContent::Binary { len, .. }) => {
+                let bytes = delta.unsigned_abs() as u64 * self.bytes_per_row;
+                let offset = if delta < 0 { self.hex_offset.saturating_sub(bytes) } else { self.hex_offset + bytes };
+                self.hex_offset = offset.min(len.saturating_sub(1));
+                return;
+            },
+            _ => 0,
+        };
+        self.scroll = self.scroll.saturating_add_signed(delta).min(len.saturating_sub(1));
+    }
+
+    fn jump_to_offset(&mut self, input: &str) -> Option<Action> {
+        let Some(&PreviewContent::Binary { len, .. }) = self.current() else {
+            return None;
+        };
+        let input = input.trim();
+        let offset = match input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => input.parse(),
+        };
+        let Ok(offset) = offset else {
+            return Some(Action::Error(format!("Invalid offset: {input}")));
+        };
+        let offset = offset.min(len.saturating_sub(1));
+        self.hex_offset = offset;
+        self.hex_mark = Some(offset);
+        None
+    }
+
+    /// Draws the rendered `image` in `area`, or asks for it to be rendered if it is for another file or size.
+    fn draw_image(&mut self, buf: &mut Buffer, area: Rect) {
+        let Some(path) = self.selected.clone() else {
+            return;
+        };
+        // A rewritten image comes with a fresh preview, and is rendered again.
+        let modified = self.cache.get(&path).and_then(|preview| preview.modified);
+        let image = match &self.image {
+            Some(image)
+                if (&image.path, image.modified, image.width, image.height)
+                    == (&path, modified, area.width, area.height) =>
+            {
+                image
+            },
+            _ => {
+                let request = (path, modified, area.width, area.height);
+                if self.image_requested.as_ref() != Some(&request) {
+                    self.previewer.load_image(&request.0, modified, area.width, area.height, self.image_protocol);
+                    self.image_requested = Some(request);
+                }
+                buf.set_line(area.x, area.y, &Line::from("loading…".dim()), area.width);
+                return;
+            },
+        };
+        match &image.image {
+            RenderedImage::HalfBlocks(rows) => {
+                for (y, row) in rows.iter().take(area.height as usize).enumerate() {
+                    for (x, &(top, bottom)) in row.iter().take(area.width as usize).enumerate() {
+                        buf.get_mut(area.x + x as u16, area.y + y as u16).set_symbol("▀").set_fg(top).set_bg(bottom);
+                    }
+                }
+            },
+            RenderedImage::Graphics { .. } if !matches!(self.mode, Mode::Home | Mode::Filter | Mode::Visual) => {},
+            &RenderedImage::Graphics { width, height, .. } => {
+                // Printing the cells under the image would draw over it.
+                let covered = Rect::new(area.x, area.y, width.min(area.width), height.min(area.height));
+                for y in covered.top()..covered.bottom() {
+                    for x in covered.left()..covered.right() {
+                        buf.get_mut(x, y).set_skip(true);
+                    }
+                }
+                self.graphics = Some((image.path.clone(), image.modified, covered));
+            },
+            RenderedImage::Error(error) => {
+                buf.set_line(area.x, area.y, &Line::from(error.clone().red()), area.width);
+            },
+        }
+    }
+
+    fn draw_pane(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let Some(area) = main_layout(area, self.config.layout, self.shown).preview else {
+            return Ok(());
+        };
+        let title = self.selected.as_deref().map(display_name).unwrap_or_default();
+        let block = Block::default().borders(Borders::LEFT).title(title.bold());
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        self.viewport_height = inner.height as usize;
+
+        let preview = self.selected.as_ref().and_then(|path| self.cache.get(path));
+        let lines = match preview.map(|preview| &preview.content) {
+            None if self.loading => vec![Line::from("loading…".dim())],
+            None => vec![],
+            Some(PreviewContent::Text { lines, truncated }) => self.text_lines(lines, *truncated),
+            Some(PreviewContent::Directory(entries)) => {
+                directory_lines(entries, self.config.show_hidden, self.scroll, self.viewport_height, None)
+            },
+            Some(&PreviewContent::Image { width, height }) => {
+                f.render_widget(Paragraph::new(format!("image, {width}×{height}").dim()), inner);
+                let area = Rect { y: inner.y + 1, height: inner.height.saturating_sub(1), ..inner };
+                self.draw_image(f.buffer_mut(), area);
+                return Ok(());
+            },
+            Some(&PreviewContent::Binary { len, ref head }) => {
+                let offset_width = format!("{:x}", len).len().max(8);
+                self.bytes_per_row = if inner.width as usize >= hex_row_width(16, offset_width) { 16 } else { 8 };
+                let rows = self.viewport_height.saturating_sub(1) as u64;
+                let last_row = len.saturating_sub(1) / self.bytes_per_row * self.bytes_per_row;
+                let start = (self.hex_offset / self.bytes_per_row * self.bytes_per_row).min(last_row);
+                let window = rows * self.bytes_per_row;
+                let bytes = page_slice(0, head, start, window, len).or_else(|| {
+                    let page = self.hex_page.as_ref()?;
+                    page_slice(page.offset, &page.bytes, start, window, len)
+                });
+                let mut lines = vec![Line::from(
+                    format!("binary file, {} · [Alt-g] jump to offset", human_size(len).trim_start()).dim(),
+                )];
+                match bytes {
+                    Some(bytes) => {
+                        lines.extend(hex_lines(bytes, start, self.bytes_per_row, offset_width, self.hex_mark))
+                    },
+                    None => {
+                        if self.hex_requested != Some(start) {
+                            self.hex_requested = Some(start);
+                            self.previewer.load_hex_page(self.selected.as_ref().unwrap(), start);
+                        }
+                        lines.push(Line::from("loading…".dim()));
+                    },
+                }
+                lines
+            },
+            Some(PreviewContent::Error(error)) => vec![Line::from(error.clone().red())],
+        };
+        f.render_widget(Paragraph::new(lines), inner);
+
+        Ok(())
+    }
+
+    fn text_lines(&self, lines: &[Vec<(String, Style)>], truncated: bool) -> Vec<Line<'static>> {
+        let number_width = (self.scroll + self.viewport_height).min(lines.len()).to_string().len();
+        let mut text = lines
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(self.viewport_height)
+            .map(|(i, spans)| {
+                let number = Span::raw(format!("{:>number_width$} ", i + 1)).dark_gray();
+                Line::from(
+                    std::iter::once(number)
+                        .chain(spans.iter().map(|(text, style)| Span::styled(text.clone(), *style)))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        if truncated && text.len() < self.viewport_height {
+            text.push(Line::from("… the rest of the file is not shown".dim()));
+        }
+        text
+    }
+}
+
+/// Rows of a hex dump of `bytes`, which start at `offset` in the file: the offset of each row, its bytes in hex, then
+/// as ASCII text with dots for what isn't printable. The byte at `mark` is highlighted.
+fn hex_lines(
+    bytes: &[u8],
+    offset: u64,
+    bytes_per_row: u64,
+    offset_width: usize,
+    mark: Option<u64>,
+) -> Vec<Line<'static>> {
+    bytes
+        .chunks(bytes_per_row as usize)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let row_offset = offset + row as u64 * bytes_per_row;
+            let mut hex = vec![Span::raw(format!("{row_offset:0offset_width$x} ")).dark_gray()];
+            let mut ascii = vec![Span::raw(" │").dark_gray()];
+            for (i, &byte) in chunk.iter().enumerate() {
+                let style = match byte {
+                    _ if mark == Some(row_offset + i as u64) => Style::new().reversed(),
+                    0 => Style::new().dark_gray(),
+                    byte if byte.is_ascii_graphic() || byte == b' ' => Style::new(),
+                    _ => Style::new().yellow(),
+                };
+                // Groups of eight bytes are set apart.
+                let gap = if i % 8 == 0 { "  " } else { " " };
+                hex.push(Span::raw(gap));
+                hex.push(Span::styled(format!("{byte:02x}"), style));
+                let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+                ascii.push(Span::styled(c.to_string(), style));
+            }
+            // Keep the ASCII column lined up on the last, shorter row.
+            let padding = (chunk.len()..bytes_per_row as usize).map(|i| if i % 8 == 0 { 4 } else { 3 }).sum();
+            hex.push(Span::raw(" ".repeat(padding)));
+            hex.extend(ascii);
+            Line::from(hex)
+        })
+        .collect()
+}
+
+/// Width taken by a row of [`hex_lines`].
+fn hex_row_width(bytes_per_row: u64, offset_width: usize) -> usize {
+    offset_width + 1 + bytes_per_row as usize * 3 + bytes_per_row as usize / 8 + 2 + bytes_per_row as usize
+}
+
+/// The bytes at `offset..offset + len` of a file of `file_len` bytes, if all of them are in `page`, which was read at
+/// `page_offset`.
+fn page_slice(page_offset: u64, page: &[u8], offset: u64, len: u64, file_len: u64) -> Option<&[u8]> {
+    let end = (offset + len).min(file_len);
+    if offset < page_offset || end > page_offset + page.len() as u64 {
+        return None;
+    }
+    Some(&page[(offset - page_offset) as usize..(end - page_offset) as usize])
+}
+
+/// Takes the image graphics over the `covered` cells off the screen. Kitty deletes them, sixel images are written over.
+fn erase_graphics(protocol: ImageProtocol, covered: Rect) -> std::io::Result<()> {
+    let mut out = tui::io();
+    if protocol == ImageProtocol::Kitty {
+        return out.write_all(KITTY_DELETE_ALL.as_bytes());
+    }
+    blank(&mut out, covered)
+}
+
+/// Writes blanks over the `covered` cells, which is what the frame holds under image graphics.
+fn blank(out: &mut dyn Write, covered: Rect) -> std::io::Result<()> {
+    let blank = " ".repeat(covered.width as usize);
+    for y in covered.top()..covered.bottom() {
+        out.queue(MoveTo(covered.x, y))?.queue(Print(&blank))?;
+    }
+    Ok(())
+}
+
+/// Entries of a directory, skipping dotfiles unless `show_hidden`, from the `scroll`th one on. The one named
+/// `highlighted`, if any, is shown selected.
+pub(super) fn directory_lines(
+    entries: &[(String, bool)],
+    show_hidden: bool,
+    scroll: usize,
+    height: usize,
+    highlighted: Option<&str>,
+) -> Vec<Line<'static>> {
+    let visible = entries.iter().filter(|(name, _)| show_hidden || !name.starts_with('.')).collect::<Vec<_>>();
+    if visible.is_empty() {
+        return vec![Line::from("empty directory".dim())];
+    }
+    visible
+        .into_iter()
+        .skip(scroll)
+        .take(height)
+        .map(|(name, is_dir)| {
+            let line = if *is_dir {
+                Line::from(format!("📂{name}").blue().bold())
+            } else {
+                Line::from(format!("📄{name}"))
+            };
+            if highlighted == Some(name.as_str()) {
+                line.style(Style::new().bg(Color::Magenta))
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+impl Component for Preview {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.previewer = Previewer::new(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.shown = config.preview;
+        self.image_protocol = config.image_protocol.resolve();
+        self.config = config;
+        Ok(())
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SelectionChanged(path) => self.show(path),
+            Action::PreviewLoaded(preview) => self.on_loaded(preview),
+            // The selected file may have been written to.
+            Action::Refresh => self.show(self.selected.clone()),
+            Action::ToggleHidden => self.config.show_hidden = !self.config.show_hidden,
+            Action::TogglePreview => {
+                self.shown = !self.shown;
+                self.show(self.selected.clone());
+            },
+            Action::ScrollPreviewDown => self.scroll_by(1),
+            Action::ScrollPreviewUp => self.scroll_by(-1),
+            Action::PreviewPageDown => self.scroll_by(self.viewport_height as isize),
+            Action::PreviewPageUp => self.scroll_by(-(self.viewport_height as isize)),
+            Action::SwitchMode(mode) => self.mode = mode,
+            // Resizing clears the screen.
+            Action::Resize(..) => self.graphics_shown = None,
+            Action::ImageLoaded(image) if self.selected.as_ref() == Some(&image.path) => {
+                self.image_requested = None;
+                self.image = Some(image);
+            },
+            Action::HexLoaded(page) if self.selected.as_ref() == Some(&page.path) => {
+                self.hex_requested = None;
+                self.hex_page = Some(page);
+            },
+            Action::JumpToOffset => {
+                if let (Some(path), Some(PreviewContent::Binary { .. })) = (&self.selected, self.current()) {
+                    return Ok(Some(Action::Prompt(PromptPurpose::JumpToOffset(path.clone()), "0x".to_owned())));
+                }
+            },
+            Action::PromptSubmitted(PromptPurpose::JumpToOffset(path), input)
+                if self.selected.as_ref() == Some(&path) =>
+            {
+                return Ok(self.jump_to_offset(&input));
+            },
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if f.count() != self.frame_count.wrapping_add(1) {
+            self.graphics_shown = None;
+        }
+        self.frame_count = f.count();
+        self.graphics = None;
+        self.draw_pane(f, area)?;
+        if self.graphics_shown.is_some() && self.graphics_shown != self.graphics {
+            if let Some((.., covered)) = self.graphics_shown.take() {
+                // Goes out before the frame, which is written to the same handle.
+                let _ = erase_graphics(self.image_protocol, covered);
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_over(&mut self, out: &mut dyn Write) -> Result<()> {
+        let (Some(graphics), Some(ImagePreview { image: RenderedImage::Graphics { sequence, .. }, .. })) =
+            (&self.graphics, &self.image)
+        else {
+            return Ok(());
+        };
+        if self.graphics_shown.as_ref() == Some(graphics) {
+            return Ok(());
+        }
+        // The terminal draws the image at the cursor, which is put back where the frame left it. What the last frames
+        // drew under it would show through transparent pixels.
+        let (.., covered) = graphics;
+        out.queue(SavePosition)?;
+        blank(out, *covered)?;
+        out.queue(MoveTo(covered.x, covered.y))?;
+        out.write_all(sequence.as_bytes())?;
+        out.queue(RestorePosition)?;
+        self.graphics_shown = Some(graphics.clone());
+        Ok(())
+    }
+}
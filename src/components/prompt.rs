@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Component, Frame};
+use crate::{action::Action, mode::Mode, services::fileops::display_name};
+
+/// What the text typed into a prompt is for. Sent back with [`Action::PromptSubmitted`] so the component that asked
+/// knows what to do with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PromptPurpose {
+    Rename(PathBuf),
+    CreateDir,
+    CreateFile,
+    CopyTo(Vec<PathBuf>),
+    MoveTo(Vec<PathBuf>),
+    /// Offset to show in the hex view of a binary file, in hex with a `0x` prefix or else in decimal.
+    JumpToOffset(PathBuf),
+}
+
+impl PromptPurpose {
+    fn title(&self) -> String {
+        fn describe(paths: &[PathBuf]) -> String {
+            match paths {
+                [path] => display_name(path),
+                paths => format!("{} items", paths.len()),
+            }
+        }
+        match self {
+            PromptPurpose::Rename(path) => format!("Rename {}", display_name(path)),
+            PromptPurpose::CreateDir => "New directory".to_owned(),
+            PromptPurpose::CreateFile => "New file".to_owned(),
+            PromptPurpose::CopyTo(paths) => format!("Copy {} to", describe(paths)),
+            PromptPurpose::MoveTo(paths) => format!("Move {} to", describe(paths)),
+            PromptPurpose::JumpToOffset(path) => format!("Jump to offset in {}", display_name(path)),
+        }
+    }
+}
+
+/// One of the answers offered by [`Action::Choose`], picked by pressing `key`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Choice {
+    pub key: char,
+    pub label: String,
+    pub action: Action,
+}
+
+impl Choice {
+    pub fn new(key: char, label: &str, action: Action) -> Self {
+        Self { key, label: label.to_owned(), action }
+    }
+}
+
+enum Dialog {
+    Input {
+        purpose: PromptPurpose,
+        input: String,
+    },
+    Confirm {
+        message: String,
+        action: Box<Action>,
+    },
+    /// The first choice is the default, picked with Enter.
+    Choose {
+        message: String,
+        choices: Vec<Choice>,
+    },
+}
+
+/// Small dialog asking either for a line of text ([`Action::Prompt`]), for a yes/no answer before running an action
+/// ([`Action::Confirm`]) or to pick one of a few actions ([`Action::Choose`]). Returns to the mode it was opened from
+/// when closed.
+#[derive(Default)]
+pub struct Prompt {
+    command_tx: Option<UnboundedSender<Action>>,
+    mode: Mode,
+    return_mode: Mode,
+    dialog: Option<Dialog>,
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn open(&mut self, dialog: Dialog) -> Option<Action> {
+        if self.mode != Mode::Prompt {
+            self.return_mode = self.mode;
+        }
+        self.dialog = Some(dialog);
+        Some(Action::SwitchMode(Mode::Prompt))
+    }
+
+    fn close(&mut self) -> Option<Action> {
+        self.dialog = None;
+        Some(Action::SwitchMode(self.return_mode))
+    }
+
+    fn submit(&mut self, key: Option<char>) -> Option<Action> {
+        let action = match self.dialog.take()? {
+            Dialog::Input { purpose, input } => Action::PromptSubmitted(purpose, input),
+            Dialog::Confirm { action, .. } => *action,
+            Dialog::Choose { choices, .. } => {
+                choices.into_iter().find(|choice| key.is_none_or(|key| choice.key == key))?.action
+            },
+        };
+        // Leave the prompt first so the action is handled in the mode the prompt was opened from.
+        let close = self.close();
+        if let (Some(tx), Some(close)) = (&self.command_tx, close) {
+            let _ = tx.send(close);
+            let _ = tx.send(action);
+        }
+        None
+    }
+}
+
+impl Component for Prompt {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn handle_key_events(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if self.mode != Mode::Prompt || key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return Ok(None);
+        }
+        match (&mut self.dialog, key.code) {
+            (Some(Dialog::Input { input, .. }), KeyCode::Char(c)) => input.push(c),
+            (Some(Dialog::Input { input, .. }), KeyCode::Backspace) => {
+                input.pop();
+            },
+            (Some(Dialog::Confirm { .. }), KeyCode::Char('y' | 'Y')) => return Ok(Some(Action::SubmitPrompt)),
+            (Some(Dialog::Confirm { .. }), KeyCode::Char('n' | 'N')) => return Ok(Some(Action::CancelPrompt)),
+            (Some(Dialog::Choose { choices, .. }), KeyCode::Char(c))
+                if choices.iter().any(|choice| choice.key == c) =>
+            {
+                return Ok(self.submit(Some(c)));
+            },
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::Prompt(purpose, initial) => return Ok(self.open(Dialog::Input { purpose, input: initial })),
+            Action::Confirm(message, action) => return Ok(self.open(Dialog::Confirm { message, action })),
+            Action::Choose(message, choices) => return Ok(self.open(Dialog::Choose { message, choices })),
+            _ if self.mode != Mode::Prompt => {},
+            Action::SubmitPrompt => return Ok(self.submit(None)),
+            Action::CancelPrompt => return Ok(self.close()),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        let Some(dialog) = self.dialog.as_ref().filter(|_| self.mode == Mode::Prompt) else {
+            return Ok(());
+        };
+
+        let (title, text) = match dialog {
+            Dialog::Input { purpose, input } => (purpose.title(), Text::from(format!("{input}▏"))),
+            Dialog::Confirm { message, .. } => {
+                ("Confirm".to_owned(), Text::from(Line::from(vec![Span::raw(format!("{message} ")), "[y/N]".bold()])))
+            },
+            Dialog::Choose { message, choices } => {
+                let choices = choices
+                    .iter()
+                    .flat_map(|choice| [format!("[{}]", choice.key).bold(), Span::raw(format!(" {}  ", choice.label))]);
+                (
+                    "Choose".to_owned(),
+                    Text::from(vec![Line::from(message.clone()), Line::from(choices.collect::<Vec<_>>())]),
+                )
+            },
+        };
+        let width = (area.width * 3 / 5).max(title.len() as u16 + 4).min(area.width);
+        let height = text.height() as u16 + 2;
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + area.height.saturating_sub(height) / 2,
+            width,
+            height: area.height.min(height),
+        };
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title)), area);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use color_eyre::eyre::Result;
+use ratatui::{prelude::*, widgets::*};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{centered_rect, Component, Frame};
+use crate::{
+    action::Action,
+    mode::Mode,
+    services::{
+        fileops::{display_name, FileOp},
+        trash::{self, TrashedItem},
+    },
+};
+
+/// Popup listing what is in the trash, with where each item came from and when it was trashed, to restore items or
+/// delete them for good.
+#[derive(Default)]
+pub struct Trash {
+    command_tx: Option<UnboundedSender<Action>>,
+    mode: Mode,
+    items: Vec<TrashedItem>,
+    loading: bool,
+    curr_index: usize,
+    offset: usize,
+    viewport_height: usize,
+}
+
+impl Trash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lists the trash directories on the blocking thread pool, as there may be a few slow mounts to go through.
+    fn load(&mut self) {
+        let Some(tx) = self.command_tx.clone() else {
+            return;
+        };
+        self.loading = true;
+        tokio::task::spawn_blocking(move || {
+            let _ = tx.send(Action::TrashListed(trash::list()));
+        });
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        self.curr_index = self.curr_index.saturating_add_signed(delta).min(self.items.len().saturating_sub(1));
+    }
+
+    fn purge(items: &[TrashedItem]) -> FileOp {
+        FileOp::Delete { paths: items.iter().flat_map(|item| [item.files_path(), item.info_path()]).collect() }
+    }
+
+    fn purge_selected(&self) -> Option<Action> {
+        let item = self.items.get(self.curr_index)?;
+        let message = format!("Delete {} from the trash permanently?", display_name(&item.original_path));
+        Some(Action::Confirm(message, Box::new(Action::FileOp(Self::purge(std::slice::from_ref(item))))))
+    }
+
+    fn empty(&self) -> Option<Action> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let message = format!("Empty the trash, deleting {} items permanently?", self.items.len());
+        Some(Action::Confirm(message, Box::new(Action::FileOp(Self::purge(&self.items)))))
+    }
+}
+
+impl Component for Trash {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::SwitchMode(mode) => self.mode = mode,
+            Action::OpenTrash if self.mode == Mode::Home => {
+                self.load();
+                return Ok(Some(Action::SwitchMode(Mode::Trash)));
+            },
+            Action::TrashListed(items) => {
+                self.items = items;
+                self.loading = false;
+                self.move_cursor(0);
+            },
+            _ if self.mode != Mode::Trash => {},
+            Action::Refresh => self.load(),
+            Action::CloseTrash => return Ok(Some(Action::SwitchMode(Mode::Home))),
+            Action::SelectNext => self.move_cursor(1),
+            Action::SelectPrevious => self.move_cursor(-1),
+            Action::PageDown => self.move_cursor(self.viewport_height as isize),
+            Action::PageUp => self.move_cursor(-(self.viewport_height as isize)),
+            Action::SelectFirst => self.curr_index = 0,
+            Action::SelectLast => self.move_cursor(isize::MAX),
+            Action::RestoreTrashed => {
+                let item = self.items.get(self.curr_index).cloned();
+                return Ok(item.map(|item| Action::FileOp(FileOp::Restore(vec![item]))));
+            },
+            Action::PurgeTrashed => return Ok(self.purge_selected()),
+            Action::EmptyTrash => return Ok(self.empty()),
+            _ => {},
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, f: &mut Frame<'_>, area: Rect) -> Result<()> {
+        if self.mode != Mode::Trash {
+            return Ok(());
+        }
+
+        let area = centered_rect(90, 80, area);
+        f.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title("Trash");
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let [table_area, status_area] = *Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(inner)
+        else {
+            unreachable!()
+        };
+
+        // One row goes to the table header.
+        self.viewport_height = table_area.height.saturating_sub(1) as usize;
+        let height = self.viewport_height.max(1);
+        if self.curr_index < self.offset {
+            self.offset = self.curr_index;
+        } else if self.curr_index >= self.offset + height {
+            self.offset = self.curr_index + 1 - height;
+        }
+        let rows = self.items.iter().enumerate().skip(self.offset).take(self.viewport_height).map(|(i, item)| {
+            let icon = if item.is_dir { "📂" } else { "📄" };
+            let location = item.original_path.parent().map(|parent| parent.display().to_string()).unwrap_or_default();
+            let deleted = item.deleted.map(|deleted| deleted.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default();
+            let row = Row::new([format!("{icon}{}", display_name(&item.original_path)), location, deleted]);
+            if i == self.curr_index {
+                row.style(Style::new().bg(Color::Magenta))
+            } else {
+                row
+            }
+        });
+        let widths = [Constraint::Fill(1), Constraint::Fill(2), Constraint::Length(16)];
+        let table = Table::new(rows, widths).header(Row::new(["Name", "Original location", "Deleted"]).bold());
+        f.render_widget(table, table_area);
+
+        let status = match (self.loading, self.items.len()) {
+            (true, _) => "loading…".to_owned(),
+            (false, 0) => "The trash is empty".to_owned(),
+            (false, n) => format!("{n} items"),
+        };
+        f.render_widget(Paragraph::new(status.dim()), status_area);
+
+        Ok(())
+    }
+}
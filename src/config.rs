@@ -11,7 +11,13 @@ use serde::{
 };
 use serde_json::Value as JsonValue;
 
-use crate::{action::Action, mode::Mode};
+use crate::{
+    action::Action,
+    column::Column,
+    components::LayoutMode,
+    mode::Mode,
+    services::{commands::UserCommand, image::ImageProtocol, opener::Openers},
+};
 
 const CONFIG: &str = include_str!("../.config/config.json5");
 
@@ -31,6 +37,32 @@ pub struct Config {
     pub keybindings: KeyBindings,
     #[serde(default)]
     pub styles: Styles,
+    #[serde(default)]
+    pub columns: Vec<Column>,
+    #[serde(default = "default_true")]
+    pub show_hidden: bool,
+    #[serde(default = "default_true")]
+    pub respect_gitignore: bool,
+    #[serde(default = "default_grep_max_file_size")]
+    pub grep_max_file_size: u64,
+    #[serde(default = "default_true")]
+    pub preview: bool,
+    #[serde(default)]
+    pub layout: LayoutMode,
+    #[serde(default)]
+    pub image_protocol: ImageProtocol,
+    #[serde(default)]
+    pub openers: Openers,
+    #[serde(default)]
+    pub commands: HashMap<String, UserCommand>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_grep_max_file_size() -> u64 {
+    4 * 1024 * 1024
 }
 
 impl Config {
@@ -75,6 +107,17 @@ impl Config {
             }
         }
 
+        for (pattern, commands) in default_config.openers.iter() {
+            cfg.openers.entry(pattern.clone()).or_insert_with(|| commands.clone());
+        }
+        for (name, command) in default_config.commands.iter() {
+            cfg.commands.entry(name.clone()).or_insert_with(|| command.clone());
+        }
+
+        if cfg.columns.is_empty() {
+            cfg.columns = default_config.columns;
+        }
+
         Ok(cfg)
     }
 }
@@ -428,6 +471,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::identity_op)]
     fn test_parse_color_rgb() {
         let color = parse_color("rgb123");
         let expected = 16 + 1 * 36 + 2 * 6 + 3;
@@ -0,0 +1,193 @@
+use std::fmt;
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FilterKind {
+    #[default]
+    Fuzzy,
+    Substring,
+    Glob,
+    Regex,
+}
+
+impl FilterKind {
+    /// The kind that comes after this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            FilterKind::Fuzzy => FilterKind::Substring,
+            FilterKind::Substring => FilterKind::Glob,
+            FilterKind::Glob => FilterKind::Regex,
+            FilterKind::Regex => FilterKind::Fuzzy,
+        }
+    }
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FilterKind::Fuzzy => "fuzzy",
+            FilterKind::Substring => "substring",
+            FilterKind::Glob => "glob",
+            FilterKind::Regex => "regex",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A name that matched, with how well it did and which characters to highlight.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    /// Indices of the matched characters, in `char`s rather than bytes.
+    pub indices: Vec<usize>,
+}
+
+enum Compiled {
+    Fuzzy(Box<SkimMatcherV2>),
+    Substring(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+/// Matches names against a pattern. Matching is smart-case: case-insensitive unless the pattern has an uppercase
+/// letter in it.
+pub struct Matcher {
+    compiled: Compiled,
+    pattern: String,
+    case_sensitive: bool,
+}
+
+impl Matcher {
+    pub fn new(kind: FilterKind, pattern: &str) -> Result<Self, String> {
+        let case_sensitive = pattern.chars().any(char::is_uppercase);
+        let compiled = match kind {
+            FilterKind::Fuzzy => {
+                let matcher = SkimMatcherV2::default();
+                Compiled::Fuzzy(Box::new(if case_sensitive { matcher.respect_case() } else { matcher.ignore_case() }))
+            },
+            FilterKind::Substring => {
+                Compiled::Substring(if case_sensitive { pattern.to_owned() } else { lowercase(pattern) })
+            },
+            FilterKind::Glob => {
+                Compiled::Glob(
+                    GlobBuilder::new(pattern)
+                        .case_insensitive(!case_sensitive)
+                        .build()
+                        .map_err(|e| e.kind().to_string())?
+                        .compile_matcher(),
+                )
+            },
+            FilterKind::Regex => {
+                Compiled::Regex(
+                    RegexBuilder::new(pattern).case_insensitive(!case_sensitive).build().map_err(|e| e.to_string())?,
+                )
+            },
+        };
+        Ok(Self { compiled, pattern: pattern.to_owned(), case_sensitive })
+    }
+
+    pub fn matches(&self, name: &str) -> Option<Match> {
+        match &self.compiled {
+            Compiled::Fuzzy(matcher) => {
+                matcher.fuzzy_indices(name, &self.pattern).map(|(score, indices)| Match { score, indices })
+            },
+            Compiled::Substring(needle) => {
+                // Lowercasing can turn a character into several, e.g. `İ` into `i̇`, so each character searched is
+                // traced back to the one of `name` it came from for the highlighting.
+                let mut haystack = String::with_capacity(name.len());
+                let mut origins = vec![];
+                for (i, c) in name.chars().enumerate() {
+                    if self.case_sensitive {
+                        haystack.push(c);
+                        origins.push(i);
+                    } else {
+                        haystack.extend(c.to_lowercase());
+                        origins.extend(c.to_lowercase().map(|_| i));
+                    }
+                }
+                let start = haystack.find(needle.as_str())?;
+                // Prefer matches close to the start of the name, e.g. a prefix over a match in the extension.
+                let score = -(start as i64);
+                let mut indices = char_range(&haystack, start, start + needle.len())
+                    .into_iter()
+                    .map(|i| origins[i])
+                    .collect::<Vec<_>>();
+                indices.dedup();
+                Some(Match { score, indices })
+            },
+            Compiled::Glob(matcher) => matcher.is_match(name).then(Match::default),
+            Compiled::Regex(regex) => {
+                let found = regex.find(name)?;
+                let score = -(found.start() as i64);
+                Some(Match { score, indices: char_range(name, found.start(), found.end()) })
+            },
+        }
+    }
+}
+
+/// Lowercases `s` one character at a time, the same way names are when matching them.
+fn lowercase(s: &str) -> String {
+    s.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// Converts the byte range `start..end` of `s` into the indices of the characters it covers.
+fn char_range(s: &str, start: usize, end: usize) -> Vec<usize> {
+    s.char_indices().enumerate().filter(|(_, (byte, _))| (start..end).contains(byte)).map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_fuzzy() {
+        let matcher = Matcher::new(FilterKind::Fuzzy, "crs").unwrap();
+        assert_eq!(matcher.matches("Cargo.rs").unwrap().indices, vec![0, 6, 7]);
+        assert!(matcher.matches("README.md").is_none());
+    }
+
+    #[test]
+    fn test_substring_smart_case() {
+        let matcher = Matcher::new(FilterKind::Substring, "read").unwrap();
+        assert_eq!(matcher.matches("README.md").unwrap().indices, vec![0, 1, 2, 3]);
+
+        let matcher = Matcher::new(FilterKind::Substring, "Read").unwrap();
+        assert!(matcher.matches("README.md").is_none());
+    }
+
+    #[test]
+    fn test_substring_case_folding() {
+        // `İ` lowercases to two characters, and `ẞ` to one shorter in bytes.
+        let matcher = Matcher::new(FilterKind::Substring, "stan").unwrap();
+        assert_eq!(matcher.matches("İstanbul.txt").unwrap().indices, vec![1, 2, 3, 4]);
+        let matcher = Matcher::new(FilterKind::Substring, "i̇s").unwrap();
+        assert_eq!(matcher.matches("İstanbul.txt").unwrap().indices, vec![0, 1]);
+        let matcher = Matcher::new(FilterKind::Substring, "ße.").unwrap();
+        assert_eq!(matcher.matches("STRAẞE.txt").unwrap().indices, vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn test_glob() {
+        let matcher = Matcher::new(FilterKind::Glob, "*.rs").unwrap();
+        assert!(matcher.matches("main.rs").is_some());
+        assert!(matcher.matches("main.rs.bak").is_none());
+    }
+
+    #[test]
+    fn test_regex() {
+        let matcher = Matcher::new(FilterKind::Regex, r"\d+").unwrap();
+        assert_eq!(matcher.matches("img_042.png").unwrap().indices, vec![4, 5, 6]);
+        assert!(Matcher::new(FilterKind::Regex, "(").is_err());
+    }
+
+    #[test]
+    fn test_char_range_multibyte() {
+        assert_eq!(char_range("héllo", 3, 5), vec![2, 3]);
+    }
+}
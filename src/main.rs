@@ -5,9 +5,14 @@
 pub mod action;
 pub mod app;
 pub mod cli;
+pub mod column;
 pub mod components;
 pub mod config;
+pub mod filter;
 pub mod mode;
+pub mod rename;
+pub mod services;
+pub mod sort;
 pub mod tui;
 pub mod utils;
 
@@ -26,7 +31,7 @@ async fn tokio_main() -> Result<()> {
     initialize_panic_handler()?;
 
     let args = Cli::parse();
-    let mut app = App::new(args.tick_rate, args.frame_rate)?;
+    let mut app = App::new(args.tick_rate, args.frame_rate, args.path)?;
     app.run().await?;
 
     Ok(())
@@ -4,4 +4,15 @@ use serde::{Deserialize, Serialize};
 pub enum Mode {
     #[default]
     Home,
+    Filter,
+    Visual,
+    Find,
+    Grep,
+    Prompt,
+    Jobs,
+    Trash,
+    BulkRename,
+    PatternRename,
+    Output,
+    Command,
 }
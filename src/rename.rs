@@ -0,0 +1,148 @@
+use std::fmt;
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+lazy_static! {
+    static ref NUMBER_PLACEHOLDER: Regex = Regex::new(r"\{n(?::(\d+))?\}").unwrap();
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CaseTransform {
+    #[default]
+    Keep,
+    Lower,
+    Upper,
+    Title,
+}
+
+impl CaseTransform {
+    /// The transform that comes after this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            CaseTransform::Keep => CaseTransform::Lower,
+            CaseTransform::Lower => CaseTransform::Upper,
+            CaseTransform::Upper => CaseTransform::Title,
+            CaseTransform::Title => CaseTransform::Keep,
+        }
+    }
+
+    fn apply(self, s: &str) -> String {
+        match self {
+            CaseTransform::Keep => s.to_owned(),
+            CaseTransform::Lower => s.to_lowercase(),
+            CaseTransform::Upper => s.to_uppercase(),
+            CaseTransform::Title => {
+                let mut title = String::with_capacity(s.len());
+                let mut start_of_word = true;
+                for c in s.chars() {
+                    if start_of_word {
+                        title.extend(c.to_uppercase());
+                    } else {
+                        title.extend(c.to_lowercase());
+                    }
+                    start_of_word = !c.is_alphanumeric();
+                }
+                title
+            },
+        }
+    }
+}
+
+impl fmt::Display for CaseTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CaseTransform::Keep => "keep case",
+            CaseTransform::Lower => "lowercase",
+            CaseTransform::Upper => "UPPERCASE",
+            CaseTransform::Title => "Title Case",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How to make new names out of old ones. Everything but the extension applies to the name without its extension.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RenamePattern {
+    /// Regex to replace in the name. Empty means the whole name, if there is something to replace it with.
+    pub find: String,
+    /// What matches are replaced with: `$1` or `${name}` stand for capture groups, and `{n}` or `{n:03}` for the
+    /// position of the entry, starting at 1, padded with zeros to the given width.
+    pub replace: String,
+    pub case: CaseTransform,
+    /// New extension, with or without its dot. Empty keeps the extension.
+    pub extension: String,
+}
+
+/// A compiled [`RenamePattern`].
+pub struct Renamer {
+    find: Option<Regex>,
+    replace: String,
+    case: CaseTransform,
+    extension: Option<String>,
+}
+
+impl Renamer {
+    pub fn new(pattern: &RenamePattern) -> Result<Self, String> {
+        let find = match (pattern.find.as_str(), pattern.replace.as_str()) {
+            ("", "") => None,
+            ("", _) => Some(Regex::new("(?s)^.*$").unwrap()),
+            (find, _) => Some(Regex::new(find).map_err(|e| e.to_string())?),
+        };
+        let extension = pattern.extension.trim_start_matches('.');
+        Ok(Self {
+            find,
+            replace: pattern.replace.clone(),
+            case: pattern.case,
+            extension: (!extension.is_empty()).then(|| extension.to_owned()),
+        })
+    }
+
+    /// New name of the `n`th entry, a directory if `is_dir`, whose name is `name`. Directories don't have extensions.
+    pub fn rename(&self, name: &str, is_dir: bool, n: usize) -> String {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() && !is_dir => (stem, Some(extension)),
+            _ => (name, None),
+        };
+        let stem = match &self.find {
+            Some(find) => find.replace_all(stem, number(&self.replace, n).as_str()).into_owned(),
+            None => stem.to_owned(),
+        };
+        let stem = self.case.apply(&stem);
+        match self.extension.as_deref().or(extension) {
+            Some(extension) if !is_dir => format!("{stem}.{extension}"),
+            _ => stem,
+        }
+    }
+}
+
+/// Replaces the `{n}` and `{n:03}` placeholders of `template` with `n`.
+fn number(template: &str, n: usize) -> String {
+    NUMBER_PLACEHOLDER
+        .replace_all(template, |captures: &Captures| {
+            let width = captures.get(1).and_then(|width| width.as_str().parse().ok()).unwrap_or(0);
+            format!("{n:0width$}")
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn rename(find: &str, replace: &str, case: CaseTransform, extension: &str, name: &str) -> String {
+        let pattern = RenamePattern { find: find.into(), replace: replace.into(), case, extension: extension.into() };
+        Renamer::new(&pattern).unwrap().rename(name, false, 7)
+    }
+
+    #[test]
+    fn test_rename() {
+        assert_eq!(rename("", "", CaseTransform::Keep, "", "DSC_1234.JPG"), "DSC_1234.JPG");
+        assert_eq!(rename("", "img_{n:03}", CaseTransform::Keep, "", "DSC_1234.JPG"), "img_007.JPG");
+        assert_eq!(rename(r"(\w+)_(\d+)", "$2-$1", CaseTransform::Lower, ".jpeg", "DSC_1234.JPG"), "1234-dsc.jpeg");
+        assert_eq!(rename("_", " ", CaseTransform::Title, "", "my_HOLIDAY_photo.png"), "My Holiday Photo.png");
+        assert!(Renamer::new(&RenamePattern { find: "(".into(), ..RenamePattern::default() }).is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! Background services that talk to the filesystem on behalf of the components, so that slow disks never block the
+//! render loop. Results are reported back to the application as [`Action`](crate::action::Action)s.
+
+pub mod commands;
+pub mod fileops;
+pub mod find;
+pub mod grep;
+pub mod image;
+pub mod jobs;
+pub mod journal;
+pub mod listing;
+pub mod opener;
+pub mod preview;
+pub mod trash;
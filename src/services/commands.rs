@@ -0,0 +1,358 @@
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Child, Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use super::opener::{Launch, LaunchMode};
+use crate::{action::Action, utils};
+
+/// Output streamed from a command is sent at most this often, so that chatty commands don't flood the render loop.
+const STREAM_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Where the output of a command goes once it exits.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommandOutput {
+    /// A popup over the listing.
+    #[default]
+    Popup,
+    /// `$PAGER`, which gets the terminal until it exits.
+    Pager,
+    /// Nowhere, for commands run for what they do. Failures are still reported.
+    Ignore,
+}
+
+/// A shell command from the `commands` section of the config, run by name with [`Action::RunCommand`]. Its
+/// placeholders are listed on [`Placeholders`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "UserCommandConfig")]
+pub struct UserCommand {
+    pub command: String,
+    pub output: CommandOutput,
+}
+
+/// A [`UserCommand`] as written in the config: the command alone, with its output shown in a popup, or along with
+/// where the output goes.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UserCommandConfig {
+    Command(String),
+    WithOutput {
+        command: String,
+        #[serde(default)]
+        output: CommandOutput,
+    },
+}
+
+impl From<UserCommandConfig> for UserCommand {
+    fn from(config: UserCommandConfig) -> Self {
+        match config {
+            UserCommandConfig::Command(command) => Self { command, output: CommandOutput::default() },
+            UserCommandConfig::WithOutput { command, output } => Self { command, output },
+        }
+    }
+}
+
+/// What the placeholders of a [`UserCommand`] stand for: `{file}` the selected file, `{files}` the marked files or
+/// else the selected one, `{dir}` the working directory, and `{basename}` and `{ext}` the name of the selected file
+/// without its extension and the extension. They are quoted for the shell when expanded.
+pub struct Placeholders<'a> {
+    pub file: Option<&'a Path>,
+    pub files: &'a [PathBuf],
+    pub dir: &'a Path,
+}
+
+impl UserCommand {
+    /// The command with its placeholders expanded, or why they can't be.
+    pub fn expand(&self, placeholders: &Placeholders<'_>) -> Result<String, String> {
+        let file = placeholders.file;
+        let part = |part: Option<&std::ffi::OsStr>| part.map(|part| quote(&part.to_string_lossy()));
+        let files = (!placeholders.files.is_empty()).then(|| {
+            placeholders.files.iter().map(|path| quote(&path.to_string_lossy())).collect::<Vec<_>>().join(" ")
+        });
+        let values = [
+            ("{files}", files),
+            ("{file}", file.map(|file| quote(&file.to_string_lossy()))),
+            ("{dir}", Some(quote(&placeholders.dir.to_string_lossy()))),
+            ("{basename}", file.map(|file| part(file.file_stem()).unwrap_or_default())),
+            ("{ext}", file.map(|file| part(file.extension()).unwrap_or_default())),
+        ];
+
+        // In one pass, so that nothing is expanded twice should a file name look like a placeholder.
+        let mut expanded = String::new();
+        let mut rest = self.command.as_str();
+        while let Some(start) = rest.find('{') {
+            expanded.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match values.iter().find(|(placeholder, _)| rest.starts_with(placeholder)) {
+                Some((placeholder, value)) => {
+                    expanded.push_str(value.as_deref().ok_or("No file is selected")?);
+                    rest = &rest[placeholder.len()..];
+                },
+                None => {
+                    expanded.push('{');
+                    rest = &rest[1..];
+                },
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+/// Quotes `text` for the shell, unless it is made of characters that don't need it.
+pub fn quote(text: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+=:,@%".contains(c);
+    if !text.is_empty() && text.chars().all(safe) {
+        return text.to_owned();
+    }
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Runs shell commands on the blocking thread pool, then refreshes the listing, which they may have changed. Commands
+/// from the config send their output where they say once they exit, while those typed on the command line have it
+/// streamed as they go.
+#[derive(Default)]
+pub struct CommandRunner {
+    action_tx: Option<UnboundedSender<Action>>,
+    /// Id of the last streamed command.
+    last_id: usize,
+    in_flight: Option<CancellationToken>,
+}
+
+impl CommandRunner {
+    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
+        Self { action_tx: Some(action_tx), ..Self::default() }
+    }
+
+    /// Starts the command `name` in `dir`, with its placeholders already expanded.
+    pub fn run(&self, name: &str, command: String, output: CommandOutput, dir: &Path) {
+        let Some(action_tx) = self.action_tx.clone() else {
+            return;
+        };
+        let name = name.to_owned();
+        let dir = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            for action in run(&name, &command, output, &dir) {
+                let _ = action_tx.send(action);
+            }
+        });
+    }
+
+    /// Starts `command` in `dir`, stopping the one streamed before if it is still running. It comes back as
+    /// [`Action::OutputStarted`], then lines of output as [`Action::OutputLines`] and how it exited as
+    /// [`Action::OutputDone`].
+    pub fn stream(&mut self, command: &str, dir: &Path) {
+        self.cancel();
+        let Some(action_tx) = self.action_tx.clone() else {
+            return;
+        };
+        let token = CancellationToken::new();
+        self.in_flight = Some(token.clone());
+        self.last_id += 1;
+        let id = self.last_id;
+        let _ = action_tx.send(Action::OutputStarted(id, command.to_owned()));
+        let command = command.to_owned();
+        let dir = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let result = stream(id, &command, &dir, &token, &action_tx);
+            let _ = action_tx.send(Action::OutputDone(id, result));
+            let _ = action_tx.send(Action::Refresh);
+        });
+    }
+
+    /// Kills the streamed command if it is still running, along with whatever it started.
+    pub fn cancel(&mut self) {
+        if let Some(token) = self.in_flight.take() {
+            token.cancel();
+        }
+    }
+}
+
+fn shell(command: &str, dir: &Path) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command).current_dir(dir).stdin(Stdio::null());
+    shell
+}
+
+fn run(name: &str, command: &str, output: CommandOutput, dir: &Path) -> Vec<Action> {
+    let result = shell(command, dir).output();
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => return vec![Action::Error(format!("Failed to run {name}: {e}"))],
+    };
+    let mut actions = vec![Action::Refresh];
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if !result.status.success() {
+        actions.push(Action::Error(match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("{name} exited with {}: {line}", result.status),
+            None => format!("{name} exited with {}", result.status),
+        }));
+    }
+    let text = String::from_utf8_lossy(&result.stdout).into_owned() + &stderr;
+    // A failed command with nothing to show only gets the error.
+    if text.is_empty() && !result.status.success() {
+        return actions;
+    }
+    match output {
+        CommandOutput::Popup => actions.push(Action::ShowOutput(name.to_owned(), text)),
+        CommandOutput::Pager => actions.push(page(name, &text)),
+        CommandOutput::Ignore => {},
+    }
+    actions
+}
+
+fn stream(
+    id: usize,
+    command: &str,
+    dir: &Path,
+    token: &CancellationToken,
+    action_tx: &UnboundedSender<Action>,
+) -> Result<(), String> {
+    let mut command = shell(command, dir);
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // In a group of its own, so that cancelling also stops the rest of a pipeline.
+        command.process_group(0);
+    }
+    let mut child = command.spawn().map_err(|e| format!("Failed to run sh: {e}"))?;
+
+    // Both pipes are read at once, or a command filling one of them while we wait on the other would hang.
+    let (line_tx, line_rx) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        read_lines(stdout, line_tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        read_lines(stderr, line_tx);
+    }
+    let mut lines = vec![];
+    let mut last_sent = Instant::now();
+    let mut killed = false;
+    loop {
+        match line_rx.recv_timeout(STREAM_INTERVAL) {
+            Ok(line) => lines.push(line),
+            Err(RecvTimeoutError::Timeout) => {},
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if token.is_cancelled() && !killed {
+            kill(&mut child);
+            killed = true;
+        }
+        if !lines.is_empty() && last_sent.elapsed() >= STREAM_INTERVAL {
+            let _ = action_tx.send(Action::OutputLines(id, std::mem::take(&mut lines)));
+            last_sent = Instant::now();
+        }
+    }
+    if !lines.is_empty() {
+        let _ = action_tx.send(Action::OutputLines(id, lines));
+    }
+
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if killed {
+        Err("Cancelled".to_owned())
+    } else if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Exited with {status}"))
+    }
+}
+
+/// Sends the lines read from `pipe` on a thread of their own, until it is closed.
+fn read_lines(pipe: impl Read + Send + 'static, line_tx: Sender<String>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        let mut line = vec![];
+        while reader.read_until(b'\n', &mut line).is_ok_and(|len| len > 0) {
+            let text = String::from_utf8_lossy(&line);
+            if line_tx.send(text.trim_end_matches(['\n', '\r']).to_owned()).is_err() {
+                break;
+            }
+            line.clear();
+        }
+    });
+}
+
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+}
+
+/// Writes `text` to a temporary file and shows it in `$PAGER`, or `less`, which deletes the file once the pager exits.
+fn page(name: &str, text: &str) -> Action {
+    let path = utils::create_temp_file("output", "").and_then(|(path, mut file)| {
+        file.write_all(text.as_bytes()).inspect_err(|_| {
+            let _ = fs::remove_file(&path);
+        })?;
+        Ok(path)
+    });
+    let path = match path {
+        Ok(path) => path,
+        Err(e) => return Action::Error(format!("Failed to save the output of {name}: {e}")),
+    };
+    Action::Launch(vec![Launch {
+        program: "sh".to_owned(),
+        args: vec![
+            "-c".to_owned(),
+            r#"${PAGER:-less} "$1"; status=$?; rm -f -- "$1"; exit $status"#.to_owned(),
+            "sh".to_owned(),
+            path.to_string_lossy().into_owned(),
+        ],
+        mode: LaunchMode::Suspend,
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let command = |command: &str| UserCommand { command: command.to_owned(), output: CommandOutput::Popup };
+        let files = [PathBuf::from("/home/me/a b.tar.gz"), PathBuf::from("/home/me/it's.txt")];
+        let placeholders = Placeholders { file: Some(&files[0]), files: &files, dir: Path::new("/home/me") };
+
+        assert_eq!(
+            command("tar xf {file} -C {dir}").expand(&placeholders),
+            Ok("tar xf '/home/me/a b.tar.gz' -C /home/me".to_owned())
+        );
+        assert_eq!(
+            command("du -sh {files}").expand(&placeholders),
+            Ok(r"du -sh '/home/me/a b.tar.gz' '/home/me/it'\''s.txt'".to_owned())
+        );
+        assert_eq!(command("echo {basename} {ext}").expand(&placeholders), Ok("echo 'a b.tar' gz".to_owned()));
+
+        let nothing = Placeholders { file: None, files: &[], dir: Path::new("/home/me") };
+        assert_eq!(command("make -C {dir}").expand(&nothing), Ok("make -C /home/me".to_owned()));
+        assert_eq!(command("wc -l {files}").expand(&nothing), Err("No file is selected".to_owned()));
+    }
+
+    #[test]
+    fn test_page() {
+        let Action::Launch(launches) = page("ls", "a\nb\n") else {
+            panic!("expected the pager to be launched");
+        };
+        let path = PathBuf::from(launches[0].args.last().unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\n");
+        // Someone else on the machine could read the output otherwise.
+        #[cfg(unix)]
+        assert_eq!(std::os::unix::fs::PermissionsExt::mode(&path.metadata().unwrap().permissions()) & 0o777, 0o600);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
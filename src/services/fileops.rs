@@ -0,0 +1,568 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{
+    journal::{Change, JournalEntry},
+    trash::{self, TrashedItem},
+};
+use crate::action::Action;
+
+/// What to do when a copy or a move finds something already at its target.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Conflict {
+    /// Fail the operation.
+    #[default]
+    Fail,
+    /// Leave the existing entry alone and don't copy or move that source.
+    Skip,
+    /// Replace the existing entry.
+    Overwrite,
+    /// Copy or move to a free name made by adding a numeric suffix, e.g. `notes_1.txt`.
+    Rename,
+}
+
+/// A change to the filesystem requested by the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileOp {
+    /// Copies each source into the `dest` directory, or to `dest` itself if there is a single source and `dest` is
+    /// not an existing directory.
+    Copy {
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+        conflict: Conflict,
+    },
+    /// Moves sources the same way `Copy` copies them.
+    Move {
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+        conflict: Conflict,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Renames several entries as if all at once, so that names can be swapped or shifted around, see
+    /// [`rename_all`].
+    RenameAll {
+        renames: Vec<(PathBuf, PathBuf)>,
+    },
+    Delete {
+        paths: Vec<PathBuf>,
+    },
+    CreateDir(PathBuf),
+    CreateFile(PathBuf),
+    /// Moves paths to the trash, see [`trash`](super::trash).
+    Trash {
+        paths: Vec<PathBuf>,
+    },
+    Restore(Vec<TrashedItem>),
+}
+
+impl FileOp {
+    pub fn describe(&self) -> String {
+        fn count(paths: &[PathBuf]) -> String {
+            match paths {
+                [path] => display_name(path),
+                paths => format!("{} items", paths.len()),
+            }
+        }
+        match self {
+            FileOp::Copy { sources, dest, .. } => format!("Copying {} to {}", count(sources), dest.display()),
+            FileOp::Move { sources, dest, .. } => format!("Moving {} to {}", count(sources), dest.display()),
+            FileOp::Rename { from, to } => format!("Renaming {} to {}", display_name(from), display_name(to)),
+            FileOp::RenameAll { renames } => {
+                match renames.as_slice() {
+                    [(from, to)] => format!("Renaming {} to {}", display_name(from), display_name(to)),
+                    renames => format!("Renaming {} items", renames.len()),
+                }
+            },
+            FileOp::Delete { paths } => format!("Deleting {}", count(paths)),
+            FileOp::CreateDir(path) => format!("Creating directory {}", display_name(path)),
+            FileOp::CreateFile(path) => format!("Creating file {}", display_name(path)),
+            FileOp::Trash { paths } => format!("Moving {} to the trash", count(paths)),
+            FileOp::Restore(items) => {
+                match items.as_slice() {
+                    [item] => format!("Restoring {}", display_name(&item.original_path)),
+                    items => format!("Restoring {} items", items.len()),
+                }
+            },
+        }
+    }
+
+    /// Whether the operation can take long enough that it should run as a job with progress, see
+    /// [`JobManager`](super::jobs::JobManager).
+    pub fn is_long_running(&self) -> bool {
+        matches!(self, FileOp::Copy { .. } | FileOp::Move { .. } | FileOp::Delete { .. })
+    }
+
+    /// Runs the operation right away, adding what it changed to `changes` as it goes so that whatever was done before
+    /// a failure can still be undone.
+    pub fn run(&self, changes: &mut Vec<Change>) -> io::Result<()> {
+        match self {
+            FileOp::Copy { sources, dest, conflict } => {
+                for source in sources {
+                    if let Some(target) = resolve_target(source, dest, sources.len(), *conflict)? {
+                        replace(&target, || {
+                            copy_recursive(source, &target).inspect_err(|_| {
+                                let _ = remove_existing(&target);
+                            })
+                        })?;
+                        changes.push(Change::copied(source, &target));
+                    }
+                }
+            },
+            FileOp::Move { sources, dest, conflict } => {
+                for source in sources {
+                    if let Some(target) = resolve_target(source, dest, sources.len(), *conflict)? {
+                        replace(&target, || move_path(source, &target))?;
+                        changes.push(Change::moved(source, &target));
+                    }
+                }
+            },
+            FileOp::Rename { from, to } => {
+                ensure_absent(to)?;
+                fs::rename(from, to)?;
+                changes.push(Change::moved(from, to));
+            },
+            FileOp::RenameAll { renames } => {
+                let mut done = vec![];
+                let result = rename_all(renames, &mut done);
+                if !done.is_empty() {
+                    changes.push(Change::renamed(&done));
+                }
+                result?;
+            },
+            FileOp::Delete { paths } => paths.iter().try_for_each(|path| remove(path))?,
+            FileOp::CreateDir(path) => {
+                ensure_absent(path)?;
+                // Parents are created as needed, and each of them has to go on undo.
+                let mut missing =
+                    path.ancestors().take_while(|dir| dir.symlink_metadata().is_err()).collect::<Vec<_>>();
+                missing.reverse();
+                let mut created = vec![];
+                let result = missing.iter().try_for_each(|dir| {
+                    fs::create_dir(dir)?;
+                    created.push(dir);
+                    io::Result::Ok(())
+                });
+                // Stamped once the whole chain exists, as creating each directory changes its parent.
+                changes.extend(created.into_iter().map(|dir| Change::created_dir(dir)));
+                result?;
+            },
+            FileOp::CreateFile(path) => {
+                fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+                changes.push(Change::created_file(path));
+            },
+            FileOp::Trash { paths } => {
+                for path in paths {
+                    changes.push(Change::Trashed { item: trash::trash(path)? });
+                }
+            },
+            FileOp::Restore(items) => {
+                for item in items {
+                    item.restore()?;
+                    changes.push(Change::restored(item.clone()));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Runs the operation, then reports the changes it made to the journal and the failure if any.
+    pub fn run_and_report(&self, action_tx: &UnboundedSender<Action>) {
+        let mut changes = vec![];
+        let result = self.run(&mut changes);
+        if !changes.is_empty() {
+            let _ = action_tx.send(Action::Recorded(JournalEntry { description: self.describe(), changes }));
+        }
+        if let Err(e) = result {
+            let _ = action_tx.send(Action::Error(format!("{} failed: {e}", self.describe())));
+        }
+    }
+}
+
+/// Runs `op` on the blocking thread pool, then asks for a refresh of the listing, reporting failures as
+/// [`Action::Error`].
+pub fn spawn(op: FileOp, action_tx: UnboundedSender<Action>) {
+    tokio::task::spawn_blocking(move || {
+        op.run_and_report(&action_tx);
+        let _ = action_tx.send(Action::Refresh);
+    });
+}
+
+pub fn display_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string())
+}
+
+/// Where `source` ends up when copied or moved to `dest`.
+pub fn target_path(source: &Path, dest: &Path, source_count: usize) -> PathBuf {
+    match source.file_name() {
+        Some(name) if dest.is_dir() || source_count > 1 => dest.join(name),
+        _ => dest.to_path_buf(),
+    }
+}
+
+/// Where `source` goes when copied or moved to `dest`, taking care of anything already there as `conflict` says.
+/// `None` means the source is skipped. With [`Conflict::Overwrite`], the existing target is left for the caller to
+/// replace.
+pub fn resolve_target(
+    source: &Path,
+    dest: &Path,
+    source_count: usize,
+    conflict: Conflict,
+) -> io::Result<Option<PathBuf>> {
+    let target = target_path(source, dest, source_count);
+    if target.symlink_metadata().is_err() {
+        return Ok(Some(target));
+    }
+    match conflict {
+        Conflict::Fail => ensure_absent(&target).map(|_| None),
+        Conflict::Skip => Ok(None),
+        Conflict::Overwrite if source.starts_with(&target) => {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} would overwrite itself", display_name(source))))
+        },
+        Conflict::Overwrite => Ok(Some(target)),
+        Conflict::Rename => Ok(Some(unique_path(&target))),
+    }
+}
+
+/// Returns the first of `path`, `path_1`, `path_2`… that doesn't exist, keeping the extension of files at the end.
+pub fn unique_path(path: &Path) -> PathBuf {
+    let name = display_name(path);
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !path.is_dir() => (stem, format!(".{extension}")),
+        _ => (name.as_str(), String::new()),
+    };
+    let mut candidate = path.to_path_buf();
+    let mut n = 0;
+    while candidate.symlink_metadata().is_ok() {
+        n += 1;
+        candidate = path.with_file_name(format!("{stem}_{n}{extension}"));
+    }
+    candidate
+}
+
+/// Why each rename of a batch can't be made, if it can't: because another one of the batch takes the same name, or
+/// because the name is taken by an entry that the batch doesn't rename away.
+pub fn rename_conflicts(renames: &[(PathBuf, PathBuf)]) -> Vec<Option<&'static str>> {
+    let sources = renames.iter().map(|(from, _)| from).collect::<HashSet<_>>();
+    let mut targets = HashSet::new();
+    let duplicates = renames.iter().filter(|(_, to)| !targets.insert(to)).map(|(_, to)| to).collect::<HashSet<_>>();
+    renames
+        .iter()
+        .map(|(_, to)| {
+            if duplicates.contains(to) {
+                Some("duplicate name")
+            } else if !sources.contains(to) && to.symlink_metadata().is_ok() {
+                Some("already exists")
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Renames every `from` to its `to` as if all at once, so that `a` → `b` along with `b` → `a` swaps the two. Everything
+/// is first moved out of the way under a temporary name, then to its new name. Nothing is renamed if any rename
+/// conflicts, see [`rename_conflicts`].
+///
+/// The renames made are added to `done`. Should one fail, those left go back to their old names, unless a rename made
+/// took it, in which case they stay under their temporary name and the error says which it is.
+pub fn rename_all(renames: &[(PathBuf, PathBuf)], done: &mut Vec<(PathBuf, PathBuf)>) -> io::Result<()> {
+    let conflict = renames.iter().zip(rename_conflicts(renames)).find_map(|((_, to), reason)| Some((to, reason?)));
+    if let Some((to, reason)) = conflict {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{}: {reason}", to.display())));
+    }
+    let mut staged: Vec<(&Path, PathBuf, &Path)> = vec![];
+    for (i, (from, to)) in renames.iter().enumerate() {
+        let temporary =
+            unique_path(&from.with_file_name(format!(".{}-rename-{}-{i}", env!("CARGO_PKG_NAME"), std::process::id())));
+        if let Err(e) = fs::rename(from, &temporary) {
+            for (from, temporary, _) in staged.iter().rev() {
+                let _ = fs::rename(temporary, from);
+            }
+            return Err(e);
+        }
+        staged.push((from, temporary, to));
+    }
+    let mut result = Ok(());
+    let mut left = vec![];
+    for (from, temporary, to) in staged {
+        if result.is_ok() {
+            match fs::rename(&temporary, to) {
+                Ok(()) => {
+                    done.push((from.to_path_buf(), to.to_path_buf()));
+                    continue;
+                },
+                Err(e) => result = Err(e),
+            }
+        }
+        left.push((from, temporary));
+    }
+    let Err(e) = result else {
+        return Ok(());
+    };
+    let stuck = left
+        .into_iter()
+        .filter(|(from, temporary)| ensure_absent(from).and_then(|()| fs::rename(temporary, from)).is_err())
+        .map(|(from, temporary)| format!("{} as {}", display_name(from), temporary.display()))
+        .collect::<Vec<_>>();
+    if stuck.is_empty() {
+        return Err(e);
+    }
+    Err(io::Error::new(e.kind(), format!("{e}, and left {}", stuck.join(", "))))
+}
+
+/// Where the entry at `target` is set aside while something replaces it, see [`replace`].
+pub(super) fn aside_path(target: &Path) -> PathBuf {
+    unique_path(&target.with_file_name(format!(".{}-overwrite-{}", env!("CARGO_PKG_NAME"), std::process::id())))
+}
+
+/// Puts something at `target` with `write` in place of the entry already there, which is only removed once `write`
+/// succeeded. Until then it is set aside under a temporary name, and it is put back if `write` fails without leaving
+/// anything at `target`.
+fn replace(target: &Path, write: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+    if target.symlink_metadata().is_err() {
+        return write();
+    }
+    let aside = aside_path(target);
+    fs::rename(target, &aside)?;
+    let left_aside = |e: io::Error| {
+        io::Error::new(
+            e.kind(),
+            format!("{e}, and the replaced {} was left as {}", display_name(target), aside.display()),
+        )
+    };
+    match write() {
+        Ok(()) => {
+            if let Err(e) = remove(&aside) {
+                log::error!("Failed to remove {} after replacing it: {e}", aside.display());
+            }
+            Ok(())
+        },
+        Err(e) if target.symlink_metadata().is_err() => {
+            match fs::rename(&aside, target) {
+                Ok(()) => Err(e),
+                Err(_) => Err(left_aside(e)),
+            }
+        },
+        Err(e) => Err(left_aside(e)),
+    }
+}
+
+pub(super) fn remove_existing(path: &Path) -> io::Result<()> {
+    match remove(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+pub(super) fn ensure_absent(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata().is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+    }
+    Ok(())
+}
+
+/// Copies a file, a symlink or a whole directory tree. Refuses to overwrite anything at `to`.
+pub fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    ensure_absent(to)?;
+    if to.starts_with(from) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot copy a directory into itself"));
+    }
+    let metadata = from.symlink_metadata()?;
+    if metadata.is_symlink() {
+        copy_symlink(from, to)
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+pub(super) fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+pub(super) fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Moves `from` to `to`, falling back to copying and deleting when they are on different filesystems.
+pub fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    ensure_absent(to)?;
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_recursive(from, to)?;
+            remove(from)
+        },
+        result => result,
+    }
+}
+
+/// Deletes a file, a symlink (not what it points to) or a whole directory tree.
+pub fn remove(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::services::journal::Replay;
+
+    #[test]
+    fn test_copy_and_move_tree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join("src/nested/file.txt"), "hello").unwrap();
+
+        FileOp::Copy { sources: vec![dir.join("src")], dest: dir.join("copy"), conflict: Conflict::Fail }
+            .run(&mut vec![])
+            .unwrap();
+        assert_eq!(fs::read_to_string(dir.join("copy/nested/file.txt")).unwrap(), "hello");
+
+        fs::create_dir(dir.join("dest")).unwrap();
+        FileOp::Move { sources: vec![dir.join("copy")], dest: dir.join("dest"), conflict: Conflict::Fail }
+            .run(&mut vec![])
+            .unwrap();
+        assert!(!dir.join("copy").exists());
+        assert_eq!(fs::read_to_string(dir.join("dest/copy/nested/file.txt")).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_refuses_to_overwrite() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("a"), "a").unwrap();
+        fs::write(dir.join("b"), "b").unwrap();
+
+        let rename = FileOp::Rename { from: dir.join("a"), to: dir.join("b") };
+        assert_eq!(rename.run(&mut vec![]).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert!(FileOp::CreateFile(dir.join("a")).run(&mut vec![]).is_err());
+        assert_eq!(fs::read_to_string(dir.join("b")).unwrap(), "b");
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir(dir.join("dest")).unwrap();
+        fs::write(dir.join("notes.txt"), "new").unwrap();
+        fs::write(dir.join("dest/notes.txt"), "old").unwrap();
+        let copy = |conflict| {
+            FileOp::Copy { sources: vec![dir.join("notes.txt")], dest: dir.join("dest"), conflict }
+                .run(&mut vec![])
+                .unwrap()
+        };
+
+        copy(Conflict::Skip);
+        assert_eq!(fs::read_to_string(dir.join("dest/notes.txt")).unwrap(), "old");
+        copy(Conflict::Rename);
+        copy(Conflict::Rename);
+        assert_eq!(fs::read_to_string(dir.join("dest/notes_2.txt")).unwrap(), "new");
+        copy(Conflict::Overwrite);
+        assert_eq!(fs::read_to_string(dir.join("dest/notes.txt")).unwrap(), "new");
+
+        // A move that fails leaves what it was to overwrite where it was.
+        let op = FileOp::Move {
+            sources: vec![dir.join("gone.txt")],
+            dest: dir.join("dest/notes.txt"),
+            conflict: Conflict::Overwrite,
+        };
+        assert_eq!(op.run(&mut vec![]).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(fs::read_to_string(dir.join("dest/notes.txt")).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.join("dest")).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_rename_all_swaps_and_cycles() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for name in ["a", "b", "c", "d"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let renames =
+            [("a", "b"), ("b", "c"), ("c", "a"), ("d", "e")].map(|(from, to)| (dir.join(from), dir.join(to))).to_vec();
+        assert_eq!(rename_conflicts(&renames), vec![None; 4]);
+        rename_all(&renames, &mut vec![]).unwrap();
+        for (name, contents) in [("a", "c"), ("b", "a"), ("c", "b"), ("e", "d")] {
+            assert_eq!(fs::read_to_string(dir.join(name)).unwrap(), contents);
+        }
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 4);
+
+        let renames = [("a", "x"), ("b", "x"), ("c", "e")].map(|(from, to)| (dir.join(from), dir.join(to))).to_vec();
+        assert_eq!(rename_conflicts(&renames), vec![
+            Some("duplicate name"),
+            Some("duplicate name"),
+            Some("already exists")
+        ]);
+        assert!(rename_all(&renames, &mut vec![]).is_err());
+        assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "c");
+    }
+
+    #[test]
+    fn test_undo_redo_nested_mkdir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let mut changes = vec![];
+        FileOp::CreateDir(dir.join("a/b/c")).run(&mut changes).unwrap();
+        let mut entry = JournalEntry { description: "mkdir".to_owned(), changes };
+
+        entry.apply(Replay::Undo).unwrap();
+        assert!(!dir.join("a").exists());
+        entry.apply(Replay::Redo).unwrap();
+        assert!(dir.join("a/b/c").is_dir());
+        entry.apply(Replay::Undo).unwrap();
+        assert!(!dir.join("a").exists());
+    }
+
+    #[test]
+    fn test_copy_into_itself() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir(dir.join("src")).unwrap();
+        assert!(FileOp::Copy { sources: vec![dir.join("src")], dest: dir.join("src"), conflict: Conflict::Fail }
+            .run(&mut vec![])
+            .is_err());
+    }
+
+    #[test]
+    fn test_rename_all_partly_fails() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for name in ["a", "b", "c"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        // The directory `c` would go to doesn't exist, so its rename fails after the one of `a` was made.
+        let renames =
+            [("a", "x"), ("c", "missing/c"), ("b", "y")].map(|(from, to)| (dir.join(from), dir.join(to))).to_vec();
+        let mut changes = vec![];
+
+        assert!(FileOp::RenameAll { renames }.run(&mut changes).is_err());
+        let mut names = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["b", "c", "x"]);
+        // What was renamed can still be undone.
+        assert_eq!(changes, vec![Change::renamed(&[(dir.join("a"), dir.join("x"))])]);
+    }
+}
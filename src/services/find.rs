@@ -0,0 +1,191 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ignore::{Walk, WalkBuilder};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    action::Action,
+    filter::{FilterKind, Matcher},
+};
+
+/// Results are sent back once this many have piled up, or once `BATCH_INTERVAL` has passed since the last batch so
+/// that sparse matches in a big tree still show up promptly.
+const BATCH_SIZE: usize = 256;
+pub(super) const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FindOptions {
+    pub show_hidden: bool,
+    pub respect_gitignore: bool,
+}
+
+/// Walks a directory tree on the blocking thread pool and streams back the paths whose file name matches a pattern.
+///
+/// Like [`Lister`](super::listing::Lister), starting a new search cancels the one in flight.
+#[derive(Default)]
+pub struct Finder {
+    action_tx: Option<UnboundedSender<Action>>,
+    next_id: usize,
+    in_flight: Option<CancellationToken>,
+}
+
+impl Finder {
+    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
+        Self { action_tx: Some(action_tx), ..Self::default() }
+    }
+
+    /// Starts searching below `root` and returns the id that tags the resulting [`Action::FindResults`] and
+    /// [`Action::FindDone`] actions.
+    pub fn find(&mut self, root: &Path, matcher: Arc<Matcher>, options: FindOptions) -> usize {
+        self.cancel();
+        self.next_id += 1;
+        let id = self.next_id;
+        let Some(action_tx) = self.action_tx.clone() else {
+            return id;
+        };
+        let token = CancellationToken::new();
+        self.in_flight = Some(token.clone());
+        let root = root.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            walk(&root, &matcher, options, id, &token, &action_tx);
+            let _ = action_tx.send(Action::FindDone(id));
+        });
+        id
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(token) = self.in_flight.take() {
+            token.cancel();
+        }
+    }
+}
+
+/// Walks the tree below `root` in depth-first order, skipping hidden and ignored files as configured. `.git`
+/// directories are always skipped.
+pub fn walker(root: &Path, options: FindOptions) -> Walk {
+    WalkBuilder::new(root)
+        .hidden(!options.show_hidden)
+        .git_ignore(options.respect_gitignore)
+        .git_global(options.respect_gitignore)
+        .git_exclude(options.respect_gitignore)
+        .parents(options.respect_gitignore)
+        .ignore(options.respect_gitignore)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build()
+}
+
+fn walk(
+    root: &Path,
+    matcher: &Matcher,
+    options: FindOptions,
+    id: usize,
+    token: &CancellationToken,
+    action_tx: &UnboundedSender<Action>,
+) {
+    let mut batch = vec![];
+    let mut last_sent = Instant::now();
+    for entry in walker(root, options).flatten() {
+        if token.is_cancelled() {
+            return;
+        }
+        if entry.depth() == 0 {
+            continue;
+        }
+        if matcher.matches(&entry.file_name().to_string_lossy()).is_some() {
+            batch.push(entry.into_path());
+        }
+        if !batch.is_empty() && (batch.len() >= BATCH_SIZE || last_sent.elapsed() >= BATCH_INTERVAL) {
+            let _ = action_tx.send(Action::FindResults(id, std::mem::take(&mut batch)));
+            last_sent = Instant::now();
+        }
+    }
+    if !batch.is_empty() {
+        let _ = action_tx.send(Action::FindResults(id, batch));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A git repository with a hidden directory, an ignored one and a nested file.
+    fn scratch_tree() -> TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for subdir in [".git", ".hidden", "target", "src/nested"] {
+            fs::create_dir_all(dir.join(subdir)).unwrap();
+        }
+        for file in [".git/main.rs", ".hidden/main.rs", "target/main.rs", "src/nested/main.rs", "main.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        tmp
+    }
+
+    fn walked(root: &Path, options: FindOptions) -> Vec<String> {
+        let mut paths = walker(root, options)
+            .flatten()
+            .filter(|entry| entry.depth() > 0)
+            .map(|entry| entry.path().strip_prefix(root).unwrap().display().to_string())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_walker() {
+        let tmp = scratch_tree();
+        let dir = tmp.path();
+
+        assert_eq!(walked(dir, FindOptions { show_hidden: false, respect_gitignore: true }), vec![
+            "main.rs",
+            "src",
+            "src/nested",
+            "src/nested/main.rs"
+        ]);
+        // `.git` is left out even with hidden and ignored files shown.
+        assert_eq!(walked(dir, FindOptions { show_hidden: true, respect_gitignore: false }), vec![
+            ".gitignore",
+            ".hidden",
+            ".hidden/main.rs",
+            "main.rs",
+            "src",
+            "src/nested",
+            "src/nested/main.rs",
+            "target",
+            "target/main.rs"
+        ]);
+    }
+
+    #[test]
+    fn test_walk() {
+        let tmp = scratch_tree();
+        let dir = tmp.path().join("src");
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::unbounded_channel();
+        let matcher = Matcher::new(FilterKind::Substring, "s").unwrap();
+        let options = FindOptions { show_hidden: false, respect_gitignore: true };
+
+        walk(&dir, &matcher, options, 1, &CancellationToken::new(), &action_tx);
+        let mut found = std::iter::from_fn(|| action_rx.try_recv().ok())
+            .flat_map(|action| {
+                match action {
+                    Action::FindResults(1, paths) => paths,
+                    action => panic!("unexpected {action:?}"),
+                }
+            })
+            .collect::<Vec<_>>();
+        found.sort();
+        // Matches at any depth, but not `src` itself.
+        assert_eq!(found, vec![dir.join("nested"), dir.join("nested/main.rs")]);
+    }
+}
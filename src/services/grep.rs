@@ -0,0 +1,238 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use super::find::{walker, FindOptions, BATCH_INTERVAL};
+use crate::action::Action;
+
+const BATCH_SIZE: usize = 128;
+
+/// The search stops once this many lines matched, as a query matching nearly everything in a big tree would
+/// otherwise pile up matches until memory runs out.
+pub const MAX_MATCHES: usize = 10_000;
+
+/// Number of lines shown above and below a match.
+const CONTEXT_LINES: usize = 2;
+
+/// Files with a NUL byte in this many leading bytes are considered binary and skipped.
+const BINARY_SNIFF_LEN: usize = 8192;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrepMatch {
+    pub path: PathBuf,
+    /// 1-based, like editors count them.
+    pub line_number: usize,
+    pub line: String,
+    /// Byte ranges of `line` that matched.
+    pub ranges: Vec<(usize, usize)>,
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+}
+
+/// Searches the contents of the files below a directory on the blocking thread pool and streams back the matching
+/// lines.
+///
+/// Like [`Lister`](super::listing::Lister), starting a new search cancels the one in flight.
+#[derive(Default)]
+pub struct Grepper {
+    action_tx: Option<UnboundedSender<Action>>,
+    next_id: usize,
+    in_flight: Option<CancellationToken>,
+}
+
+impl Grepper {
+    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
+        Self { action_tx: Some(action_tx), ..Self::default() }
+    }
+
+    /// Starts searching the files below `root` that are at most `max_file_size` bytes and returns the id that tags
+    /// the resulting [`Action::GrepResults`] and [`Action::GrepDone`] actions. The latter tells whether the search
+    /// stopped at [`MAX_MATCHES`].
+    pub fn search(&mut self, root: &Path, regex: Regex, max_file_size: u64, options: FindOptions) -> usize {
+        self.cancel();
+        self.next_id += 1;
+        let id = self.next_id;
+        let Some(action_tx) = self.action_tx.clone() else {
+            return id;
+        };
+        let token = CancellationToken::new();
+        self.in_flight = Some(token.clone());
+        let root = root.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            if let Some(truncated) = walk(&root, &regex, max_file_size, options, id, &token, &action_tx) {
+                let _ = action_tx.send(Action::GrepDone(id, truncated));
+            }
+        });
+        id
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(token) = self.in_flight.take() {
+            token.cancel();
+        }
+    }
+}
+
+/// Searches the files below `root`, sending the matches in batches. Returns whether the search stopped at
+/// [`MAX_MATCHES`], or `None` if it was cancelled.
+fn walk(
+    root: &Path,
+    regex: &Regex,
+    max_file_size: u64,
+    options: FindOptions,
+    id: usize,
+    token: &CancellationToken,
+    action_tx: &UnboundedSender<Action>,
+) -> Option<bool> {
+    let mut batch = vec![];
+    let mut last_sent = Instant::now();
+    let mut total = 0;
+    let mut truncated = false;
+    for entry in walker(root, options).flatten() {
+        if token.is_cancelled() {
+            return None;
+        }
+        let is_small_file =
+            entry.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.len() <= max_file_size);
+        if is_small_file {
+            // One more than the cap tells whether there was anything past it.
+            total += search_file(entry.path(), regex, MAX_MATCHES + 1 - total, &mut batch);
+        }
+        truncated = total > MAX_MATCHES;
+        if truncated {
+            batch.pop();
+            break;
+        }
+        if !batch.is_empty() && (batch.len() >= BATCH_SIZE || last_sent.elapsed() >= BATCH_INTERVAL) {
+            let _ = action_tx.send(Action::GrepResults(id, std::mem::take(&mut batch)));
+            last_sent = Instant::now();
+        }
+    }
+    if !batch.is_empty() {
+        let _ = action_tx.send(Action::GrepResults(id, batch));
+    }
+    Some(truncated)
+}
+
+/// Adds the lines of the file at `path` that match to `matches`, at most `limit` of them, and returns how many.
+/// Binary files are skipped.
+fn search_file(path: &Path, regex: &Regex, limit: usize, matches: &mut Vec<GrepMatch>) -> usize {
+    let Ok(bytes) = std::fs::read(path) else {
+        return 0;
+    };
+    if is_binary(&bytes) {
+        return 0;
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut found = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if found == limit {
+            break;
+        }
+        let ranges = regex.find_iter(line).map(|found| (found.start(), found.end())).collect::<Vec<_>>();
+        if ranges.is_empty() {
+            continue;
+        }
+        let to_owned = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+        matches.push(GrepMatch {
+            path: path.to_path_buf(),
+            line_number: i + 1,
+            line: line.to_string(),
+            ranges,
+            context_before: to_owned(&lines[i.saturating_sub(CONTEXT_LINES)..i]),
+            context_after: to_owned(&lines[i + 1..(i + 1 + CONTEXT_LINES).min(lines.len())]),
+        });
+        found += 1;
+    }
+    found
+}
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_search_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "todo: first\ntwo\nthree\nfour\nfive\nsix\nlast todo, todo").unwrap();
+        let regex = Regex::new("todo").unwrap();
+        let mut matches = vec![];
+
+        assert_eq!(search_file(&path, &regex, usize::MAX, &mut matches), 2);
+        // The context stops at the start and the end of the file.
+        assert_eq!(matches[0], GrepMatch {
+            path: path.clone(),
+            line_number: 1,
+            line: "todo: first".to_owned(),
+            ranges: vec![(0, 4)],
+            context_before: vec![],
+            context_after: vec!["two".to_owned(), "three".to_owned()],
+        });
+        assert_eq!(matches[1], GrepMatch {
+            path: path.clone(),
+            line_number: 7,
+            line: "last todo, todo".to_owned(),
+            ranges: vec![(5, 9), (11, 15)],
+            context_before: vec!["five".to_owned(), "six".to_owned()],
+            context_after: vec![],
+        });
+
+        matches.clear();
+        assert_eq!(search_file(&path, &regex, 1, &mut matches), 1);
+        assert_eq!(matches[0].line_number, 1);
+    }
+
+    #[test]
+    fn test_skips_binary_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("data.bin");
+        std::fs::write(&path, b"todo\0\x01\x02").unwrap();
+        let mut matches = vec![];
+
+        assert!(is_binary(&std::fs::read(&path).unwrap()));
+        assert_eq!(search_file(&path, &Regex::new("todo").unwrap(), usize::MAX, &mut matches), 0);
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn test_max_matches() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let options = FindOptions { show_hidden: false, respect_gitignore: false };
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut found = || {
+            let truncated =
+                walk(dir, &Regex::new("x").unwrap(), u64::MAX, options, 1, &CancellationToken::new(), &action_tx);
+            let results = std::iter::from_fn(|| action_rx.try_recv().ok())
+                .map(|action| {
+                    match action {
+                        Action::GrepResults(1, results) => results.len(),
+                        action => panic!("unexpected {action:?}"),
+                    }
+                })
+                .sum::<usize>();
+            (results, truncated)
+        };
+
+        std::fs::write(dir.join("a.txt"), "x\n".repeat(MAX_MATCHES)).unwrap();
+        assert_eq!(found(), (MAX_MATCHES, Some(false)));
+        std::fs::write(dir.join("b.txt"), "x\n").unwrap();
+        assert_eq!(found(), (MAX_MATCHES, Some(true)));
+    }
+}
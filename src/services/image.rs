@@ -0,0 +1,206 @@
+use std::{env, fmt::Write, io::Cursor, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{imageops::FilterType, DynamicImage, ImageError, ImageFormat, ImageReader, Rgba, RgbaImage};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+/// Formats previewed as pictures, anything else is left to the text and hex views.
+const FORMATS: [ImageFormat; 4] = [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::Gif, ImageFormat::WebP];
+/// Size of a cell in pixels when the terminal doesn't tell.
+const DEFAULT_CELL_SIZE: (u32, u32) = (8, 16);
+/// The kitty protocol takes image data in chunks of at most this many bytes.
+const KITTY_CHUNK_SIZE: usize = 4096;
+/// Deletes the images drawn with the kitty protocol, which text drawn over them doesn't.
+pub const KITTY_DELETE_ALL: &str = "\x1b_Ga=d,d=A,q=2\x1b\\";
+
+/// How images are drawn in the terminal.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageProtocol {
+    /// Whatever the terminal seems to support, going by its environment variables.
+    #[default]
+    Auto,
+    Kitty,
+    Sixel,
+    /// Two pixels a cell with the `▀` character, which works everywhere.
+    HalfBlocks,
+}
+
+impl ImageProtocol {
+    pub fn resolve(self) -> Self {
+        if self != ImageProtocol::Auto {
+            return self;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+        // Multiplexers don't pass graphics through without extra setup.
+        if env::var_os("TMUX").is_some() || term.starts_with("screen") || term.starts_with("tmux") {
+            ImageProtocol::HalfBlocks
+        } else if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || term == "xterm-ghostty"
+            || program == "WezTerm"
+            || program == "ghostty"
+        {
+            ImageProtocol::Kitty
+        } else if ["foot", "mlterm", "contour", "yaft"].iter().any(|name| term.starts_with(name))
+            || program == "iTerm.app"
+        {
+            ImageProtocol::Sixel
+        } else {
+            ImageProtocol::HalfBlocks
+        }
+    }
+}
+
+/// An image scaled down and encoded to be drawn in the preview pane.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RenderedImage {
+    /// Colors of the upper and lower half of each cell, row by row.
+    HalfBlocks(Vec<Vec<(Color, Color)>>),
+    /// Escape sequence drawing the image at the cursor, and how many columns and rows the image covers.
+    Graphics {
+        sequence: String,
+        width: u16,
+        height: u16,
+    },
+    Error(String),
+}
+
+pub fn is_image(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| FORMATS.contains(&format))
+}
+
+/// Decodes the image at `path` and scales it down to fit in `width` by `height` cells.
+pub fn render(path: &Path, width: u16, height: u16, protocol: ImageProtocol) -> RenderedImage {
+    try_render(path, width.max(1) as u32, height.max(1) as u32, protocol)
+        .unwrap_or_else(|e| RenderedImage::Error(e.to_string()))
+}
+
+fn try_render(path: &Path, width: u32, height: u32, protocol: ImageProtocol) -> Result<RenderedImage, ImageError> {
+    let image = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+    if protocol == ImageProtocol::Kitty || protocol == ImageProtocol::Sixel {
+        let (cell_width, cell_height) = cell_size();
+        let image = fit(image, width * cell_width, height * cell_height);
+        let columns = image.width().div_ceil(cell_width) as u16;
+        let rows = image.height().div_ceil(cell_height) as u16;
+        let sequence = match protocol {
+            ImageProtocol::Kitty => kitty(&image, columns, rows)?,
+            _ => sixel(&image.to_rgba8()),
+        };
+        return Ok(RenderedImage::Graphics { sequence, width: columns, height: rows });
+    }
+    let image = fit(image, width, height * 2).to_rgba8();
+    let color = |x, y| {
+        match image.get_pixel_checked(x, y) {
+            Some(&Rgba([r, g, b, a])) if a >= 128 => Color::Rgb(r, g, b),
+            _ => Color::Reset,
+        }
+    };
+    let rows = (0..image.height())
+        .step_by(2)
+        .map(|y| (0..image.width()).map(|x| (color(x, y), color(x, y + 1))).collect())
+        .collect();
+    Ok(RenderedImage::HalfBlocks(rows))
+}
+
+/// Size of a cell in pixels, as reported by the terminal.
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => {
+            ((size.width / size.columns) as u32, (size.height / size.rows) as u32)
+        },
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+/// Scales `image` down, keeping its aspect ratio, if it doesn't fit in `width` by `height` pixels.
+fn fit(image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if image.width() <= width && image.height() <= height {
+        return image;
+    }
+    image.resize(width, height, FilterType::Triangle)
+}
+
+/// Sends `image` as PNG over the kitty graphics protocol, in place of any image drawn before, and shows it at the
+/// cursor over `columns` by `rows` cells without moving the cursor.
+fn kitty(image: &DynamicImage, columns: u16, rows: u16) -> Result<String, ImageError> {
+    let mut png = vec![];
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    let data = STANDARD.encode(png);
+    let chunks = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect::<Vec<_>>();
+    let mut sequence = KITTY_DELETE_ALL.to_owned();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            let _ = write!(sequence, "\x1b_Ga=T,f=100,q=2,C=1,c={columns},r={rows},m={more};");
+        } else {
+            let _ = write!(sequence, "\x1b_Gm={more};");
+        }
+        sequence.push_str(std::str::from_utf8(chunk).unwrap());
+        sequence.push_str("\x1b\\");
+    }
+    Ok(sequence)
+}
+
+/// Encodes `image` as sixels, with its colors reduced to a 6×6×6 cube and the transparent pixels left alone.
+fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let level = |value: u8| (value as u32 * 5 + 127) / 255;
+    let colors = image
+        .pixels()
+        .map(|&Rgba([r, g, b, a])| (a >= 128).then(|| (level(r) * 36 + level(g) * 6 + level(b)) as usize))
+        .collect::<Vec<_>>();
+
+    let mut sequence = format!("\x1bP0;1;0q\"1;1;{width};{height}");
+    let mut used = [false; 216];
+    for color in colors.iter().flatten() {
+        used[*color] = true;
+    }
+    for color in (0..216).filter(|&color| used[color]) {
+        let _ = write!(sequence, "#{color};2;{};{};{}", color / 36 * 20, color / 6 % 6 * 20, color % 6 * 20);
+    }
+    // Each band of six rows is painted once per color in it, going back to the start of the band in between.
+    for top in (0..height).step_by(6) {
+        let mut bands: Vec<Option<Vec<u8>>> = vec![None; 216];
+        for dy in 0..6.min(height - top) {
+            for x in 0..width {
+                if let Some(color) = colors[((top + dy) * width + x) as usize] {
+                    bands[color].get_or_insert_with(|| vec![0; width as usize])[x as usize] |= 1 << dy;
+                }
+            }
+        }
+        for (color, band) in bands.iter().enumerate() {
+            let Some(band) = band else {
+                continue;
+            };
+            let _ = write!(sequence, "#{color}");
+            for run in band.chunk_by(|a, b| a == b) {
+                let c = (63 + run[0]) as char;
+                if run.len() > 3 {
+                    let _ = write!(sequence, "!{}{c}", run.len());
+                } else {
+                    sequence.extend(std::iter::repeat_n(c, run.len()));
+                }
+            }
+            sequence.push('$');
+        }
+        sequence.push('-');
+    }
+    sequence.push_str("\x1b\\");
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_sixel() {
+        let mut image = RgbaImage::from_pixel(5, 2, Rgba([255, 0, 0, 255]));
+        image.put_pixel(4, 1, Rgba([0, 0, 0, 0]));
+        assert_eq!(sixel(&image), "\x1bP0;1;0q\"1;1;5;2#180;2;100;0;0#180!4B@$-\x1b\\");
+    }
+}
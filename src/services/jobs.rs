@@ -0,0 +1,681 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    fileops::{self, Conflict, FileOp},
+    journal::{Change, JournalEntry},
+};
+use crate::action::Action;
+
+/// Progress is reported at most this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a paused or stuck job sleeps before checking whether it was resumed or cancelled.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const CHUNK_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobProgress {
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    /// Average over the time the job was running, leaving out the time it was paused or waiting for the user.
+    pub bytes_per_sec: u64,
+    pub current: Option<PathBuf>,
+}
+
+/// What to do about a file a job failed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureChoice {
+    Retry,
+    Skip,
+    Abort,
+}
+
+struct JobHandle {
+    token: CancellationToken,
+    paused: Arc<AtomicBool>,
+    choices: Sender<FailureChoice>,
+}
+
+/// Runs the file operations that can take a while (copy, move and delete) on the blocking thread pool, several at a
+/// time.
+///
+/// A job announces itself with [`Action::JobStarted`], reports [`Action::JobProgress`] while it runs and
+/// [`Action::JobDone`] when it is over. When it fails on a file it sends [`Action::JobFailed`] and waits for a
+/// [`FailureChoice`] passed to [`JobManager::resolve`].
+#[derive(Default)]
+pub struct JobManager {
+    action_tx: Option<UnboundedSender<Action>>,
+    next_id: usize,
+    jobs: HashMap<usize, JobHandle>,
+}
+
+impl JobManager {
+    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
+        Self { action_tx: Some(action_tx), ..Self::default() }
+    }
+
+    pub fn start(&mut self, op: FileOp) -> usize {
+        self.next_id += 1;
+        let id = self.next_id;
+        let Some(action_tx) = self.action_tx.clone() else {
+            return id;
+        };
+        let token = CancellationToken::new();
+        let paused = Arc::new(AtomicBool::new(false));
+        let (choices_tx, choices_rx) = mpsc::channel();
+        self.jobs.insert(id, JobHandle { token: token.clone(), paused: paused.clone(), choices: choices_tx });
+        let _ = action_tx.send(Action::JobStarted(id, op.describe()));
+        tokio::task::spawn_blocking(move || {
+            let mut worker = Worker::new(id, action_tx.clone(), token, paused, choices_rx);
+            let result = worker.run(&op);
+            if !worker.changes.is_empty() {
+                let changes = std::mem::take(&mut worker.changes);
+                let _ = action_tx.send(Action::Recorded(JournalEntry { description: op.describe(), changes }));
+            }
+            if let Err(Stop::Failed(e)) = result {
+                let _ = action_tx.send(Action::Error(format!("{} failed: {e}", op.describe())));
+            }
+            let _ = action_tx.send(Action::JobDone(id));
+            let _ = action_tx.send(Action::Refresh);
+        });
+        id
+    }
+
+    pub fn set_paused(&self, id: usize, paused: bool) {
+        if let Some(job) = self.jobs.get(&id) {
+            job.paused.store(paused, Ordering::Relaxed);
+        }
+    }
+
+    pub fn cancel(&self, id: usize) {
+        if let Some(job) = self.jobs.get(&id) {
+            job.token.cancel();
+        }
+    }
+
+    /// Cancels every job, so that quitting doesn't wait for them to finish.
+    pub fn cancel_all(&self) {
+        for job in self.jobs.values() {
+            job.token.cancel();
+        }
+    }
+
+    pub fn resolve(&self, id: usize, choice: FailureChoice) {
+        if let Some(job) = self.jobs.get(&id) {
+            let _ = job.choices.send(choice);
+        }
+    }
+
+    /// Forgets about a job once it reported [`Action::JobDone`].
+    pub fn remove(&mut self, id: usize) {
+        self.jobs.remove(&id);
+    }
+}
+
+/// One thing a job does to one file, so that a failure can be retried or skipped without redoing the rest.
+enum Step {
+    CreateDir {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    CopyFile {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    CopySymlink {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    RemoveFile(PathBuf),
+    RemoveDir(PathBuf),
+    /// Renames the entry a source overwrites out of the way, so that it can be put back if the source doesn't make it
+    /// there whole.
+    SetAside {
+        source: PathBuf,
+        target: PathBuf,
+        aside: PathBuf,
+    },
+    /// Removes the entry set aside for `source` once the source is at its target, or puts it back if part of the
+    /// source was skipped.
+    Replaced {
+        source: PathBuf,
+    },
+    /// Adds a change to the journal once the steps for `source` before it are done, unless it was skipped.
+    Record {
+        source: PathBuf,
+        change: Change,
+    },
+}
+
+impl Step {
+    fn source(&self) -> &Path {
+        match self {
+            Step::CreateDir { from, .. }
+            | Step::CopyFile { from, .. }
+            | Step::CopySymlink { from, .. }
+            | Step::Rename { from, .. }
+            | Step::RemoveFile(from)
+            | Step::RemoveDir(from)
+            | Step::SetAside { source: from, .. }
+            | Step::Replaced { source: from }
+            | Step::Record { source: from, .. } => from,
+        }
+    }
+
+    /// Whether the step counts towards the files of the progress, as opposed to directories.
+    fn is_file(&self) -> bool {
+        !matches!(
+            self,
+            Step::CreateDir { .. }
+                | Step::RemoveDir(_)
+                | Step::SetAside { .. }
+                | Step::Replaced { .. }
+                | Step::Record { .. }
+        )
+    }
+}
+
+enum Stop {
+    /// Cancelled, or aborted after a failure.
+    Cancelled,
+    /// The job could not even start, or could not put back an entry it was to overwrite.
+    Failed(io::Error),
+}
+
+struct Worker {
+    id: usize,
+    action_tx: UnboundedSender<Action>,
+    token: CancellationToken,
+    paused: Arc<AtomicBool>,
+    choices: Receiver<FailureChoice>,
+    progress: JobProgress,
+    last_report: Instant,
+    /// Time spent actually working, for the throughput.
+    busy: Duration,
+    busy_since: Instant,
+    /// Sources of the steps the user chose to skip. Nothing below them is copied, and nothing leading to them is
+    /// removed, so that a move never deletes a file it didn't manage to copy.
+    skipped: Vec<PathBuf>,
+    /// What the job changed so far, for the journal.
+    changes: Vec<Change>,
+    /// Target being overwritten by the source copied or moved at the moment, and where it was set aside.
+    replaced: Option<(PathBuf, PathBuf)>,
+}
+
+impl Worker {
+    fn new(
+        id: usize,
+        action_tx: UnboundedSender<Action>,
+        token: CancellationToken,
+        paused: Arc<AtomicBool>,
+        choices: Receiver<FailureChoice>,
+    ) -> Self {
+        Self {
+            id,
+            action_tx,
+            token,
+            paused,
+            choices,
+            progress: JobProgress::default(),
+            last_report: Instant::now(),
+            busy: Duration::ZERO,
+            busy_since: Instant::now(),
+            skipped: vec![],
+            changes: vec![],
+            replaced: None,
+        }
+    }
+
+    fn run(&mut self, op: &FileOp) -> Result<(), Stop> {
+        let steps = match self.plan(op) {
+            Ok(steps) => steps,
+            Err(_) if self.token.is_cancelled() => return Err(Stop::Cancelled),
+            Err(e) => return Err(Stop::Failed(e)),
+        };
+        self.report(true);
+        for step in &steps {
+            if let Err(stop) = self.run_step(step) {
+                // Stopped before the source got to its target, so it doesn't replace anything.
+                if let Err(e) = self.settle_replaced(false) {
+                    return Err(Stop::Failed(e));
+                }
+                return Err(stop);
+            }
+        }
+        self.progress.current = None;
+        self.report(true);
+        Ok(())
+    }
+
+    /// Works out every step of the job up front, which also gives the totals for the progress.
+    fn plan(&mut self, op: &FileOp) -> io::Result<Vec<Step>> {
+        let mut steps = vec![];
+        match op {
+            FileOp::Copy { sources, dest, conflict } => {
+                for source in sources {
+                    let Some(target) = self.plan_target(source, dest, sources.len(), *conflict, &mut steps)? else {
+                        continue;
+                    };
+                    self.plan_copy(source, &target, &mut steps)?;
+                    steps.push(Step::Replaced { source: source.clone() });
+                    steps.push(Step::Record { source: source.clone(), change: Change::copied(source, &target) });
+                }
+            },
+            FileOp::Move { sources, dest, conflict } => {
+                for source in sources {
+                    let Some(target) = self.plan_target(source, dest, sources.len(), *conflict, &mut steps)? else {
+                        continue;
+                    };
+                    if same_filesystem(source, &target) {
+                        self.progress.files_total += 1;
+                        steps.push(Step::Rename { from: source.clone(), to: target.clone() });
+                        steps.push(Step::Replaced { source: source.clone() });
+                    } else {
+                        self.plan_copy(source, &target, &mut steps)?;
+                        steps.push(Step::Replaced { source: source.clone() });
+                        self.plan_remove(source, &mut steps)?;
+                    }
+                    steps.push(Step::Record { source: source.clone(), change: Change::moved(source, &target) });
+                }
+            },
+            FileOp::Delete { paths } => {
+                for path in paths {
+                    self.plan_remove(path, &mut steps)?;
+                }
+            },
+            op => return op.run(&mut self.changes).map(|_| steps),
+        }
+        Ok(steps)
+    }
+
+    /// Resolves where `source` goes, planning the removal of whatever it overwrites.
+    fn plan_target(
+        &mut self,
+        source: &Path,
+        dest: &Path,
+        source_count: usize,
+        conflict: Conflict,
+        steps: &mut Vec<Step>,
+    ) -> io::Result<Option<PathBuf>> {
+        let Some(target) = fileops::resolve_target(source, dest, source_count, conflict)? else {
+            return Ok(None);
+        };
+        if target.starts_with(source) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot copy a directory into itself"));
+        }
+        if target.symlink_metadata().is_ok() {
+            let aside = fileops::aside_path(&target);
+            steps.push(Step::SetAside { source: source.to_path_buf(), target: target.clone(), aside });
+        }
+        Ok(Some(target))
+    }
+
+    fn plan_copy(&mut self, from: &Path, to: &Path, steps: &mut Vec<Step>) -> io::Result<()> {
+        self.check_cancelled()?;
+        let metadata = from.symlink_metadata()?;
+        let (from, to) = (from.to_path_buf(), to.to_path_buf());
+        if metadata.is_symlink() {
+            self.progress.files_total += 1;
+            steps.push(Step::CopySymlink { from, to });
+        } else if metadata.is_dir() {
+            let entries = fs::read_dir(&from)?.collect::<io::Result<Vec<_>>>()?;
+            steps.push(Step::CreateDir { from, to: to.clone() });
+            for entry in entries {
+                self.plan_copy(&entry.path(), &to.join(entry.file_name()), steps)?;
+            }
+        } else {
+            self.progress.files_total += 1;
+            self.progress.bytes_total += metadata.len();
+            steps.push(Step::CopyFile { from, to });
+        }
+        Ok(())
+    }
+
+    /// Removes directories after their contents.
+    fn plan_remove(&mut self, path: &Path, steps: &mut Vec<Step>) -> io::Result<()> {
+        self.check_cancelled()?;
+        if path.symlink_metadata()?.is_dir() {
+            for entry in fs::read_dir(path)? {
+                self.plan_remove(&entry?.path(), steps)?;
+            }
+            steps.push(Step::RemoveDir(path.to_path_buf()));
+        } else {
+            self.progress.files_total += 1;
+            steps.push(Step::RemoveFile(path.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    fn check_cancelled(&self) -> io::Result<()> {
+        if self.token.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        Ok(())
+    }
+
+    fn run_step(&mut self, step: &Step) -> Result<(), Stop> {
+        let source = step.source();
+        if let Step::Record { change, .. } = step {
+            if !self.skipped.iter().any(|skipped| source.starts_with(skipped)) {
+                self.changes.push(change.clone().stamped());
+            }
+            return Ok(());
+        }
+        if let Step::Replaced { .. } = step {
+            let whole = !self.skipped.iter().any(|skipped| skipped.starts_with(source) || source.starts_with(skipped));
+            if !whole && self.replaced.is_some() {
+                // What got to the target goes away again, so nothing else is done or recorded for the source.
+                self.skipped.push(source.to_path_buf());
+            }
+            return self.settle_replaced(whole).map_err(Stop::Failed);
+        }
+        let skip = match step {
+            Step::RemoveFile(path) | Step::RemoveDir(path) => {
+                self.skipped.iter().any(|skipped| skipped.starts_with(path) || path.starts_with(skipped))
+            },
+            _ => self.skipped.iter().any(|skipped| source.starts_with(skipped)),
+        };
+        if skip {
+            self.skipped.push(source.to_path_buf());
+            return Ok(());
+        }
+        self.progress.current = Some(source.to_path_buf());
+        loop {
+            self.wait_while_paused()?;
+            let bytes_before = self.progress.bytes_done;
+            match self.execute(step) {
+                Ok(()) => {
+                    if step.is_file() {
+                        self.progress.files_done += 1;
+                    }
+                    self.report(false);
+                    return Ok(());
+                },
+                Err(_) if self.token.is_cancelled() => return Err(Stop::Cancelled),
+                Err(e) => {
+                    self.progress.bytes_done = bytes_before;
+                    match self.ask(source, e)? {
+                        FailureChoice::Retry => continue,
+                        FailureChoice::Skip => {
+                            self.skipped.push(source.to_path_buf());
+                            return Ok(());
+                        },
+                        FailureChoice::Abort => return Err(Stop::Cancelled),
+                    }
+                },
+            }
+        }
+    }
+
+    fn execute(&mut self, step: &Step) -> io::Result<()> {
+        match step {
+            Step::CreateDir { from, to } => {
+                fs::create_dir(to)?;
+                fs::set_permissions(to, from.metadata()?.permissions())
+            },
+            Step::CopyFile { from, to } => self.copy_file(from, to),
+            Step::CopySymlink { from, to } => fileops::copy_symlink(from, to),
+            Step::Rename { from, to } => fs::rename(from, to),
+            Step::SetAside { target, aside, .. } => {
+                fileops::ensure_absent(aside)?;
+                fs::rename(target, aside)?;
+                self.replaced = Some((target.clone(), aside.clone()));
+                Ok(())
+            },
+            Step::Replaced { .. } | Step::Record { .. } => Ok(()),
+            Step::RemoveFile(path) => fs::remove_file(path),
+            Step::RemoveDir(path) => fs::remove_dir(path),
+        }
+    }
+
+    /// Removes the entry set aside for the current source when `keep_new`, or else removes what made it to the target
+    /// and puts the entry back.
+    fn settle_replaced(&mut self, keep_new: bool) -> io::Result<()> {
+        let Some((target, aside)) = self.replaced.take() else {
+            return Ok(());
+        };
+        let result = if keep_new {
+            fileops::remove(&aside)
+        } else {
+            fileops::remove_existing(&target).and_then(|()| fs::rename(&aside, &target))
+        };
+        result.map_err(|e| {
+            io::Error::new(e.kind(), format!("the replaced {} was left as {}: {e}", target.display(), aside.display()))
+        })
+    }
+
+    /// Copies a file in chunks, so that progress can be reported and the job paused or cancelled in the middle of a
+    /// big file.
+    fn copy_file(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut reader = fs::File::open(from)?;
+        let mut writer = fs::OpenOptions::new().write(true).create_new(true).open(to)?;
+        // Only what was created here is removed when the copy fails, never a file that was already in the way.
+        let result = self.copy_contents(&mut reader, &mut writer);
+        if result.is_err() {
+            let _ = fs::remove_file(to);
+        }
+        result
+    }
+
+    fn copy_contents(&mut self, reader: &mut fs::File, writer: &mut fs::File) -> io::Result<()> {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            if self.wait_while_paused().is_err() {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+            }
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..n])?;
+            self.progress.bytes_done += n as u64;
+            self.report(false);
+        }
+        writer.set_permissions(reader.metadata()?.permissions())
+    }
+
+    fn wait_while_paused(&mut self) -> Result<(), Stop> {
+        if self.paused.load(Ordering::Relaxed) {
+            self.stop_clock();
+            self.report(true);
+            while self.paused.load(Ordering::Relaxed) && !self.token.is_cancelled() {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            self.busy_since = Instant::now();
+        }
+        if self.token.is_cancelled() {
+            return Err(Stop::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Reports a failure and waits for the user to decide what to do about it.
+    fn ask(&mut self, path: &Path, error: io::Error) -> Result<FailureChoice, Stop> {
+        self.stop_clock();
+        self.report(true);
+        let _ = self.action_tx.send(Action::JobFailed(self.id, path.to_path_buf(), error.to_string()));
+        let choice = loop {
+            match self.choices.recv_timeout(POLL_INTERVAL) {
+                Ok(choice) => break choice,
+                Err(RecvTimeoutError::Timeout) if !self.token.is_cancelled() => {},
+                Err(_) => return Err(Stop::Cancelled),
+            }
+        };
+        self.busy_since = Instant::now();
+        Ok(choice)
+    }
+
+    fn stop_clock(&mut self) {
+        self.busy += self.busy_since.elapsed();
+        self.busy_since = Instant::now();
+    }
+
+    fn report(&mut self, force: bool) {
+        if !force && self.last_report.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last_report = Instant::now();
+        let busy = (self.busy + self.busy_since.elapsed()).as_secs_f64();
+        if busy > 0.0 {
+            self.progress.bytes_per_sec = (self.progress.bytes_done as f64 / busy) as u64;
+        }
+        let _ = self.action_tx.send(Action::JobProgress(self.id, self.progress.clone()));
+    }
+}
+
+#[cfg(unix)]
+fn same_filesystem(source: &Path, target: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let target_dir = target.parent().unwrap_or(target);
+    match (source.symlink_metadata(), target_dir.metadata()) {
+        (Ok(source), Ok(target_dir)) => source.dev() == target_dir.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(source: &Path, target: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    use super::*;
+
+    /// A directory with a `src` directory holding the files `a`, `b` and `c`.
+    fn scratch_dir() -> TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join("src")).unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(tmp.path().join("src").join(name), name).unwrap();
+        }
+        tmp
+    }
+
+    fn worker() -> (Worker, UnboundedReceiver<Action>, Sender<FailureChoice>) {
+        let (action_tx, action_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (choices_tx, choices_rx) = mpsc::channel();
+        let worker = Worker::new(1, action_tx, CancellationToken::new(), Arc::new(AtomicBool::new(false)), choices_rx);
+        (worker, action_rx, choices_tx)
+    }
+
+    /// Runs `steps`, putting a file in the way of the copy of `src/b` right before it.
+    fn run_with_conflict(worker: &mut Worker, steps: &[Step], dst: &Path) {
+        for step in steps {
+            if matches!(step, Step::CopyFile { from, .. } if from.ends_with("b")) {
+                fs::write(dst.join("b"), "in the way").unwrap();
+            }
+            assert!(worker.run_step(step).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_skip() {
+        let tmp = scratch_dir();
+        let dir = tmp.path();
+        let (mut worker, mut action_rx, choices_tx) = worker();
+        let op = FileOp::Copy { sources: vec![dir.join("src")], dest: dir.join("dst"), conflict: Conflict::Fail };
+        let steps = worker.plan(&op).ok().unwrap();
+        choices_tx.send(FailureChoice::Skip).unwrap();
+
+        run_with_conflict(&mut worker, &steps, &dir.join("dst"));
+        assert_eq!(fs::read_to_string(dir.join("dst/a")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dir.join("dst/b")).unwrap(), "in the way");
+        assert_eq!(fs::read_to_string(dir.join("dst/c")).unwrap(), "c");
+        assert_eq!(worker.progress.files_done, 2);
+        let failed = std::iter::from_fn(|| action_rx.try_recv().ok())
+            .find(|action| matches!(action, Action::JobFailed(1, path, _) if *path == dir.join("src/b")));
+        assert!(failed.is_some());
+    }
+
+    #[test]
+    fn test_retry() {
+        let tmp = scratch_dir();
+        let dir = tmp.path();
+        let (mut worker, mut action_rx, choices_tx) = worker();
+        // The directory copied to doesn't exist yet.
+        let op = FileOp::Copy { sources: vec![dir.join("src/a")], dest: dir.join("dst/a"), conflict: Conflict::Fail };
+        let job = std::thread::spawn(move || worker.run(&op).is_ok());
+
+        while !matches!(action_rx.blocking_recv(), Some(Action::JobFailed(..))) {}
+        fs::create_dir(dir.join("dst")).unwrap();
+        choices_tx.send(FailureChoice::Retry).unwrap();
+        assert!(job.join().unwrap());
+        assert_eq!(fs::read_to_string(dir.join("dst/a")).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let tmp = scratch_dir();
+        let dir = tmp.path();
+        let target = dir.join("dst/src");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("old"), "old").unwrap();
+        let entries =
+            || fs::read_dir(dir.join("dst")).unwrap().map(|entry| entry.unwrap().file_name()).collect::<Vec<_>>();
+        let op = FileOp::Copy { sources: vec![dir.join("src")], dest: dir.join("dst"), conflict: Conflict::Overwrite };
+
+        // Part of the source is skipped, so the target it was to replace is put back.
+        let (mut skipping, _action_rx, choices_tx) = worker();
+        let steps = skipping.plan(&op).ok().unwrap();
+        choices_tx.send(FailureChoice::Skip).unwrap();
+        run_with_conflict(&mut skipping, &steps, &target);
+        assert_eq!(fs::read_to_string(target.join("old")).unwrap(), "old");
+        assert!(!target.join("a").exists());
+        assert!(skipping.changes.is_empty());
+        assert_eq!(entries(), ["src"]);
+
+        let (mut overwriting, _action_rx, _choices_tx) = worker();
+        assert!(overwriting.run(&op).is_ok());
+        assert!(!target.join("old").exists());
+        assert_eq!(fs::read_to_string(target.join("b")).unwrap(), "b");
+        assert_eq!(overwriting.changes.len(), 1);
+        assert_eq!(entries(), ["src"]);
+    }
+
+    #[test]
+    fn test_skipped_copy_keeps_its_source() {
+        let tmp = scratch_dir();
+        let dir = tmp.path();
+        let (mut worker, _action_rx, choices_tx) = worker();
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        // What a move across filesystems plans: a copy, then the removal of the source.
+        let mut steps = vec![];
+        worker.plan_copy(&src, &dst, &mut steps).ok().unwrap();
+        worker.plan_remove(&src, &mut steps).ok().unwrap();
+        steps.push(Step::Record { source: src.clone(), change: Change::moved(&src, &dst) });
+        choices_tx.send(FailureChoice::Skip).unwrap();
+
+        run_with_conflict(&mut worker, &steps, &dst);
+        assert!(!src.join("a").exists() && !src.join("c").exists());
+        assert_eq!(fs::read_to_string(src.join("b")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(dst.join("a")).unwrap(), "a");
+        // The move isn't whole, so there is nothing to undo it with.
+        assert!(worker.changes.is_empty());
+    }
+}
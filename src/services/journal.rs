@@ -0,0 +1,494 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use super::{
+    fileops,
+    trash::{self, TrashedItem},
+};
+
+/// Entries beyond this many are dropped from the bottom of the undo stack.
+const MAX_ENTRIES: usize = 100;
+
+/// What an entry looked like right after a change, to tell whether it was touched since.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Stamp {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+    /// Digest of the stamps of everything below a directory, for changes whose undo deletes the whole tree. Editing
+    /// a file deep inside doesn't show in the directory's own metadata.
+    #[serde(default)]
+    contents: Option<u64>,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = path.symlink_metadata().ok()?;
+        Some(Self {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            contents: None,
+        })
+    }
+
+    /// Like [`Stamp::of`], also covering what is below a directory.
+    fn of_tree(path: &Path) -> Option<Self> {
+        let mut stamp = Self::of(path)?;
+        if stamp.is_dir {
+            let mut hasher = DefaultHasher::new();
+            hash_tree(path, &mut hasher).ok()?;
+            stamp.contents = Some(hasher.finish());
+        }
+        Some(stamp)
+    }
+}
+
+fn hash_tree(dir: &Path, hasher: &mut DefaultHasher) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let stamp = Stamp::of(&path).ok_or_else(|| changed(&path))?;
+        entry.file_name().hash(hasher);
+        stamp.hash(hasher);
+        if stamp.is_dir {
+            hash_tree(&path, hasher)?;
+        }
+    }
+    Ok(())
+}
+
+/// A reversible change made by a file operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Change {
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+        stamp: Stamp,
+    },
+    /// Renames made together by [`fileops::rename_all`], which have to be undone together as well for swaps to work.
+    Renamed {
+        renames: Vec<(PathBuf, PathBuf)>,
+        stamps: Vec<Stamp>,
+    },
+    Copied {
+        from: PathBuf,
+        to: PathBuf,
+        stamp: Stamp,
+    },
+    CreatedDir {
+        path: PathBuf,
+        stamp: Stamp,
+    },
+    CreatedFile {
+        path: PathBuf,
+        stamp: Stamp,
+    },
+    Trashed {
+        item: TrashedItem,
+    },
+    Restored {
+        item: TrashedItem,
+        stamp: Stamp,
+    },
+}
+
+impl Change {
+    pub fn moved(from: &Path, to: &Path) -> Self {
+        Self::Moved { from: from.to_path_buf(), to: to.to_path_buf(), stamp: Stamp::default() }.stamped()
+    }
+
+    pub fn renamed(renames: &[(PathBuf, PathBuf)]) -> Self {
+        Self::Renamed { renames: renames.to_vec(), stamps: vec![] }.stamped()
+    }
+
+    pub fn copied(from: &Path, to: &Path) -> Self {
+        Self::Copied { from: from.to_path_buf(), to: to.to_path_buf(), stamp: Stamp::default() }.stamped()
+    }
+
+    pub fn created_dir(path: &Path) -> Self {
+        Self::CreatedDir { path: path.to_path_buf(), stamp: Stamp::default() }.stamped()
+    }
+
+    pub fn created_file(path: &Path) -> Self {
+        Self::CreatedFile { path: path.to_path_buf(), stamp: Stamp::default() }.stamped()
+    }
+
+    pub fn restored(item: TrashedItem) -> Self {
+        Self::Restored { item, stamp: Stamp::default() }.stamped()
+    }
+
+    /// Records what the entry the change produced looks like now. Done once the change is made or remade.
+    pub fn stamped(mut self) -> Self {
+        let (path, stamp) = match &mut self {
+            // Undoing a copy deletes the whole tree, so nothing in it may have changed.
+            Change::Copied { to, stamp, .. } => {
+                *stamp = Stamp::of_tree(to).unwrap_or_default();
+                return self;
+            },
+            Change::Moved { to: path, stamp, .. }
+            | Change::CreatedDir { path, stamp }
+            | Change::CreatedFile { path, stamp } => (path.clone(), stamp),
+            Change::Restored { item, stamp } => (item.original_path.clone(), stamp),
+            Change::Renamed { renames, stamps } => {
+                *stamps = renames.iter().map(|(_, to)| Stamp::of(to).unwrap_or_default()).collect();
+                return self;
+            },
+            Change::Trashed { .. } => return self,
+        };
+        *stamp = Stamp::of(&path).unwrap_or_default();
+        self
+    }
+
+    /// Checks that `path` is still what the change left there, down to what is below it if the stamp covers that.
+    fn check_unchanged(path: &Path, stamp: &Stamp) -> io::Result<()> {
+        let now = if stamp.contents.is_some() { Stamp::of_tree(path) } else { Stamp::of(path) };
+        if now.as_ref() != Some(stamp) {
+            return Err(changed(path));
+        }
+        Ok(())
+    }
+
+    fn check_undo(&self) -> io::Result<()> {
+        match self {
+            Change::Moved { from, to, stamp } => {
+                Self::check_unchanged(to, stamp)?;
+                fileops::ensure_absent(from)
+            },
+            Change::Renamed { renames, stamps } => {
+                let targets = renames.iter().map(|(_, to)| to).collect::<HashSet<_>>();
+                for ((from, to), stamp) in renames.iter().zip(stamps) {
+                    Self::check_unchanged(to, stamp)?;
+                    if !targets.contains(from) {
+                        fileops::ensure_absent(from)?;
+                    }
+                }
+                Ok(())
+            },
+            Change::Copied { to: path, stamp, .. }
+            | Change::CreatedDir { path, stamp }
+            | Change::CreatedFile { path, stamp } => Self::check_unchanged(path, stamp),
+            Change::Trashed { item } => {
+                fileops::ensure_absent(&item.original_path)?;
+                item.files_path().symlink_metadata().map(|_| ()).map_err(|_| changed(&item.files_path()))
+            },
+            Change::Restored { item, stamp } => Self::check_unchanged(&item.original_path, stamp),
+        }
+    }
+
+    fn undo(&mut self) -> io::Result<()> {
+        match self {
+            Change::Moved { from, to, stamp } => {
+                fileops::move_path(to, from)?;
+                // Moving across filesystems copies, which doesn't keep the modification time.
+                *stamp = Stamp::of(from).unwrap_or_default();
+                Ok(())
+            },
+            Change::Renamed { renames, stamps } => {
+                rename_all_or_nothing(
+                    &renames.iter().map(|(from, to)| (to.clone(), from.clone())).collect::<Vec<_>>(),
+                )?;
+                *stamps = renames.iter().map(|(from, _)| Stamp::of(from).unwrap_or_default()).collect();
+                Ok(())
+            },
+            Change::Copied { to, .. } => fileops::remove(to),
+            Change::CreatedDir { path, .. } => fs::remove_dir(path),
+            Change::CreatedFile { path, .. } => fs::remove_file(path),
+            Change::Trashed { item } => item.restore(),
+            Change::Restored { item, .. } => {
+                *item = trash::trash(&item.original_path)?;
+                Ok(())
+            },
+        }
+    }
+
+    fn replay(&mut self, direction: Replay) -> io::Result<()> {
+        match direction {
+            Replay::Undo => self.undo(),
+            Replay::Redo => self.redo(),
+        }
+    }
+
+    fn check_redo(&self) -> io::Result<()> {
+        match self {
+            Change::Moved { from, to, stamp } => {
+                Self::check_unchanged(from, stamp)?;
+                fileops::ensure_absent(to)
+            },
+            Change::Renamed { renames, stamps } => {
+                let sources = renames.iter().map(|(from, _)| from).collect::<HashSet<_>>();
+                for ((from, to), stamp) in renames.iter().zip(stamps) {
+                    Self::check_unchanged(from, stamp)?;
+                    if !sources.contains(to) {
+                        fileops::ensure_absent(to)?;
+                    }
+                }
+                Ok(())
+            },
+            Change::Copied { from, to, .. } => {
+                from.symlink_metadata().map_err(|_| changed(from))?;
+                fileops::ensure_absent(to)
+            },
+            Change::CreatedDir { path, .. } | Change::CreatedFile { path, .. } => fileops::ensure_absent(path),
+            Change::Trashed { item } => {
+                item.original_path.symlink_metadata().map(|_| ()).map_err(|_| changed(&item.original_path))
+            },
+            Change::Restored { item, .. } => {
+                fileops::ensure_absent(&item.original_path)?;
+                item.files_path().symlink_metadata().map(|_| ()).map_err(|_| changed(&item.files_path()))
+            },
+        }
+    }
+
+    fn redo(&mut self) -> io::Result<()> {
+        match self {
+            Change::Moved { from, to, .. } => fileops::move_path(from, to),
+            Change::Renamed { renames, .. } => rename_all_or_nothing(renames),
+            Change::Copied { from, to, .. } => fileops::copy_recursive(from, to),
+            Change::CreatedDir { path, .. } => fs::create_dir(path),
+            Change::CreatedFile { path, .. } => {
+                fs::OpenOptions::new().write(true).create_new(true).open(path).map(|_| ())
+            },
+            Change::Trashed { item } => {
+                *item = trash::trash(&item.original_path)?;
+                Ok(())
+            },
+            Change::Restored { item, .. } => item.restore(),
+        }
+    }
+}
+
+/// Like [`fileops::rename_all`], but renames back those it made should one fail, for the change to stay whole.
+fn rename_all_or_nothing(renames: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    let mut done = vec![];
+    fileops::rename_all(renames, &mut done).inspect_err(|_| {
+        let _ = fileops::rename_all(
+            &done.iter().map(|(from, to)| (to.clone(), from.clone())).collect::<Vec<_>>(),
+            &mut vec![],
+        );
+    })
+}
+
+fn changed(path: &Path) -> io::Error {
+    io::Error::other(format!("{} has changed since", path.display()))
+}
+
+/// Whether a journal entry is being undone or redone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Replay {
+    Undo,
+    Redo,
+}
+
+impl Replay {
+    pub fn opposite(self) -> Self {
+        match self {
+            Replay::Undo => Replay::Redo,
+            Replay::Redo => Replay::Undo,
+        }
+    }
+}
+
+/// The changes made by one file operation, undone and redone together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub description: String,
+    pub changes: Vec<Change>,
+}
+
+impl JournalEntry {
+    /// Undoes or redoes the changes, last one first when undoing. Nothing is touched unless every change can be
+    /// reverted, that is unless nothing the operation produced was modified, moved or replaced since.
+    ///
+    /// Should one still fail, those replayed before it are taken back so that the entry can be tried again as a
+    /// whole. Any that can't be taken back either are dropped from the entry, being replayed for good.
+    pub fn apply(&mut self, direction: Replay) -> io::Result<()> {
+        let order = match direction {
+            Replay::Undo => {
+                self.changes.iter().rev().try_for_each(Change::check_undo)?;
+                (0..self.changes.len()).rev().collect::<Vec<_>>()
+            },
+            Replay::Redo => {
+                self.changes.iter().try_for_each(Change::check_redo)?;
+                (0..self.changes.len()).collect()
+            },
+        };
+        let mut replayed = vec![];
+        let result = order.iter().try_for_each(|&i| {
+            self.changes[i].replay(direction)?;
+            replayed.push(i);
+            io::Result::Ok(())
+        });
+        let Err(e) = result else {
+            self.stamp_redone(direction, &replayed);
+            return Ok(());
+        };
+        let mut taken_back = vec![];
+        for &i in replayed.iter().rev() {
+            if self.changes[i].replay(direction.opposite()).is_ok() {
+                taken_back.push(i);
+            }
+        }
+        self.stamp_redone(direction.opposite(), &taken_back);
+        let mut i = 0;
+        self.changes.retain(|_| {
+            let keep = !replayed.contains(&i) || taken_back.contains(&i);
+            i += 1;
+            keep
+        });
+        Err(e)
+    }
+
+    /// Stamps the changes at `indices` if they were just redone. Done once all of them are, as a change can touch
+    /// what an earlier one produced, e.g. creating a directory in one just created.
+    fn stamp_redone(&mut self, direction: Replay, indices: &[usize]) {
+        if direction == Replay::Redo {
+            for &i in indices {
+                self.changes[i] = self.changes[i].clone().stamped();
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks of file operations, saved to a file after every change so that undo works across restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    undo: Vec<JournalEntry>,
+    redo: Vec<JournalEntry>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Journal {
+    /// Loads the journal saved at `path`, starting afresh if there is none or it can't be read.
+    pub fn load(path: PathBuf) -> Self {
+        let journal = fs::read(&path).ok().and_then(|contents| serde_json::from_slice::<Journal>(&contents).ok());
+        Self { path: Some(path), ..journal.unwrap_or_default() }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec(self).map_err(io::Error::from).and_then(|contents| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)
+        });
+        if let Err(e) = result {
+            log::error!("Failed to save the journal to {}: {e}", path.display());
+        }
+    }
+
+    /// Records a new operation, which makes whatever was undone impossible to redo.
+    pub fn record(&mut self, entry: JournalEntry) {
+        self.redo.clear();
+        self.push(Replay::Undo, entry);
+    }
+
+    /// Takes the entry to undo or redo next.
+    pub fn pop(&mut self, direction: Replay) -> Option<JournalEntry> {
+        let entry = match direction {
+            Replay::Undo => self.undo.pop(),
+            Replay::Redo => self.redo.pop(),
+        };
+        self.save();
+        entry
+    }
+
+    /// Puts an entry on the undo or redo stack.
+    pub fn push(&mut self, direction: Replay, entry: JournalEntry) {
+        let stack = match direction {
+            Replay::Undo => &mut self.undo,
+            Replay::Redo => &mut self.redo,
+        };
+        stack.push(entry);
+        if stack.len() > MAX_ENTRIES {
+            stack.remove(0);
+        }
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_undo_redo_refuses_changed_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("a"), "a").unwrap();
+        fs::rename(dir.join("a"), dir.join("b")).unwrap();
+        let mut entry = JournalEntry {
+            description: "rename".to_owned(),
+            changes: vec![Change::moved(&dir.join("a"), &dir.join("b"))],
+        };
+
+        entry.apply(Replay::Undo).unwrap();
+        assert!(dir.join("a").exists() && !dir.join("b").exists());
+        entry.apply(Replay::Redo).unwrap();
+        assert!(!dir.join("a").exists() && dir.join("b").exists());
+
+        fs::write(dir.join("b"), "changed").unwrap();
+        assert!(entry.apply(Replay::Undo).is_err());
+        assert_eq!(fs::read_to_string(dir.join("b")).unwrap(), "changed");
+    }
+
+    #[test]
+    fn test_undo_copy_refuses_edited_contents() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join("src/nested/notes.txt"), "notes").unwrap();
+        fileops::copy_recursive(&dir.join("src"), &dir.join("copy")).unwrap();
+        let mut entry = JournalEntry {
+            description: "copy".to_owned(),
+            changes: vec![Change::copied(&dir.join("src"), &dir.join("copy"))],
+        };
+
+        // The directories themselves look the same after a file deep inside was edited.
+        fs::write(dir.join("copy/nested/notes.txt"), "edited notes").unwrap();
+        assert!(entry.apply(Replay::Undo).is_err());
+        assert_eq!(fs::read_to_string(dir.join("copy/nested/notes.txt")).unwrap(), "edited notes");
+
+        fs::remove_dir_all(dir.join("copy")).unwrap();
+        entry.apply(Replay::Redo).unwrap();
+        entry.apply(Replay::Undo).unwrap();
+        assert!(!dir.join("copy").exists());
+    }
+
+    #[test]
+    fn test_failed_undo_is_taken_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir(dir.join("made")).unwrap();
+        fs::write(dir.join("made/kept"), "").unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+        // Stamped with something in it, so that the check passes but removing the directory fails.
+        let mut entry = JournalEntry {
+            description: "create".to_owned(),
+            changes: vec![Change::created_dir(&dir.join("made")), Change::created_file(&dir.join("file"))],
+        };
+
+        assert!(entry.apply(Replay::Undo).is_err());
+        assert!(dir.join("file").exists());
+        assert_eq!(entry.changes.len(), 2);
+        // The file created again was stamped anew, and still counts as unchanged.
+        entry.changes[1].check_undo().unwrap();
+    }
+}
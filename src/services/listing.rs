@@ -0,0 +1,216 @@
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::action::Action;
+
+/// Number of entries sent back to the UI per [`Action::ListingBatch`].
+const BATCH_SIZE: usize = 512;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EntryKind {
+    Dir,
+    File,
+    Symlink,
+    BlockDevice,
+    CharDevice,
+    Fifo,
+    Socket,
+    #[default]
+    Unknown,
+}
+
+impl From<std::fs::FileType> for EntryKind {
+    fn from(file_type: std::fs::FileType) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if file_type.is_block_device() {
+                return Self::BlockDevice;
+            } else if file_type.is_char_device() {
+                return Self::CharDevice;
+            } else if file_type.is_fifo() {
+                return Self::Fifo;
+            } else if file_type.is_socket() {
+                return Self::Socket;
+            }
+        }
+        if file_type.is_symlink() {
+            Self::Symlink
+        } else if file_type.is_dir() {
+            Self::Dir
+        } else if file_type.is_file() {
+            Self::File
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirEntry {
+    pub path: PathBuf,
+    pub name: String,
+    /// Whether the entry is a directory or a symlink pointing at one.
+    pub is_dir: bool,
+    pub kind: EntryKind,
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    /// Permission bits, i.e. `st_mode` without the file type.
+    pub mode: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub link_target: Option<PathBuf>,
+}
+
+impl DirEntry {
+    fn from_std(entry: &std::fs::DirEntry, names: &mut NameCache) -> Self {
+        let path = entry.path();
+        // `DirEntry::metadata` doesn't follow symlinks, so this describes the link itself.
+        let metadata = entry.metadata().ok();
+        let kind = metadata.as_ref().map(|metadata| EntryKind::from(metadata.file_type())).unwrap_or_default();
+        let (is_dir, link_target) = match kind {
+            EntryKind::Symlink => (path.is_dir(), std::fs::read_link(&path).ok()),
+            _ => (kind == EntryKind::Dir, None),
+        };
+        let mut dir_entry = Self {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            is_dir,
+            kind,
+            size: metadata.as_ref().filter(|metadata| !metadata.is_dir()).map(|metadata| metadata.len()),
+            modified: metadata.as_ref().and_then(|metadata| metadata.modified().ok()),
+            link_target,
+            path,
+            ..Self::default()
+        };
+        #[cfg(unix)]
+        if let Some(metadata) = &metadata {
+            use std::os::unix::fs::MetadataExt;
+            dir_entry.mode = Some(metadata.mode() & 0o7777);
+            dir_entry.owner = names.user(metadata.uid());
+            dir_entry.group = names.group(metadata.gid());
+        }
+        dir_entry
+    }
+}
+
+/// Resolves user and group ids to names, remembering the answers for the duration of a listing since most entries
+/// of a directory share the same owner.
+#[derive(Default)]
+struct NameCache {
+    users: HashMap<u32, Option<String>>,
+    groups: HashMap<u32, Option<String>>,
+}
+
+#[cfg(unix)]
+impl NameCache {
+    fn user(&mut self, uid: u32) -> Option<String> {
+        self.users
+            .entry(uid)
+            .or_insert_with(|| {
+                let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+                let mut buf = vec![0 as libc::c_char; 4096];
+                let mut result = std::ptr::null_mut();
+                let ret = unsafe { libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+                if ret != 0 || result.is_null() {
+                    return Some(uid.to_string());
+                }
+                Some(unsafe { CStr::from_ptr(passwd.pw_name) }.to_string_lossy().into_owned())
+            })
+            .clone()
+    }
+
+    fn group(&mut self, gid: u32) -> Option<String> {
+        self.groups
+            .entry(gid)
+            .or_insert_with(|| {
+                let mut group: libc::group = unsafe { std::mem::zeroed() };
+                let mut buf = vec![0 as libc::c_char; 4096];
+                let mut result = std::ptr::null_mut();
+                let ret = unsafe { libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result) };
+                if ret != 0 || result.is_null() {
+                    return Some(gid.to_string());
+                }
+                Some(unsafe { CStr::from_ptr(group.gr_name) }.to_string_lossy().into_owned())
+            })
+            .clone()
+    }
+}
+
+/// Lists directories on the blocking thread pool of the tokio runtime and streams the entries back in batches.
+///
+/// Only one listing is in flight at a time: starting a new one cancels the previous one.
+#[derive(Default)]
+pub struct Lister {
+    action_tx: Option<UnboundedSender<Action>>,
+    next_id: usize,
+    in_flight: Option<CancellationToken>,
+}
+
+impl Lister {
+    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
+        Self { action_tx: Some(action_tx), ..Self::default() }
+    }
+
+    /// Starts listing `path` and returns the id that tags the resulting [`Action::ListingBatch`] and
+    /// [`Action::ListingDone`] actions.
+    pub fn list(&mut self, path: &Path) -> usize {
+        self.cancel();
+        self.next_id += 1;
+        let id = self.next_id;
+        let Some(action_tx) = self.action_tx.clone() else {
+            return id;
+        };
+        let token = CancellationToken::new();
+        self.in_flight = Some(token.clone());
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = read_dir(&path, id, &token, &action_tx) {
+                let _ = action_tx.send(Action::Error(format!("Failed to list {}: {e}", path.display())));
+            }
+            let _ = action_tx.send(Action::ListingDone(id));
+        });
+        id
+    }
+
+    /// Cancels the listing in flight, if any. Batches that were already sent may still arrive.
+    pub fn cancel(&mut self) {
+        if let Some(token) = self.in_flight.take() {
+            token.cancel();
+        }
+    }
+}
+
+fn read_dir(
+    path: &Path,
+    id: usize,
+    token: &CancellationToken,
+    action_tx: &UnboundedSender<Action>,
+) -> std::io::Result<()> {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut names = NameCache::default();
+    for entry in path.read_dir()?.flatten() {
+        if token.is_cancelled() {
+            return Ok(());
+        }
+        batch.push(DirEntry::from_std(&entry, &mut names));
+        if batch.len() == BATCH_SIZE {
+            let _ = action_tx.send(Action::ListingBatch(
+                id,
+                Arc::new(std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE))),
+            ));
+        }
+    }
+    if !batch.is_empty() && !token.is_cancelled() {
+        let _ = action_tx.send(Action::ListingBatch(id, Arc::new(batch)));
+    }
+    Ok(())
+}
@@ -0,0 +1,646 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use derive_deref::{Deref, DerefMut};
+use globset::Glob;
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::fileops::display_name;
+use crate::{action::Action, components::prompt::Choice};
+
+/// Files with none of the known extensions are taken for text if there is no NUL byte this close to their start.
+const SNIFF_BYTES: u64 = 8 * 1024;
+/// The "open with" dialog offers this many applications at most, picked with the digit keys.
+const MAX_CHOICES: usize = 9;
+
+/// How a launched program shares the terminal with us.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaunchMode {
+    /// A GUI program, left to run on its own.
+    Detached,
+    /// A terminal program, which gets the terminal until it exits.
+    #[default]
+    Suspend,
+    /// A program that has no use for the terminal but is waited for, e.g. to convert files.
+    Block,
+}
+
+/// A program to run, with its arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Launch {
+    pub program: String,
+    pub args: Vec<String>,
+    pub mode: LaunchMode,
+}
+
+impl Launch {
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+
+    /// Starts the program in a process group of its own, away from the terminal, without waiting for it.
+    pub fn spawn_detached(&self) -> io::Result<()> {
+        let mut command = self.command();
+        command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        let mut child = command.spawn()?;
+        // Reap it whenever it exits.
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    /// Runs the program away from the terminal until it exits, failing with the last line of its error output if it
+    /// does.
+    pub fn run_blocking(&self) -> Result<(), String> {
+        let output =
+            self.command().stdin(Stdio::null()).output().map_err(|e| format!("Failed to run {}: {e}", self.program))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(line) => Err(format!("{} exited with {}: {line}", self.program, output.status)),
+            None => Err(format!("{} exited with {}", self.program, output.status)),
+        }
+    }
+}
+
+/// A command opening files, from the `openers` section of the config. In its template, `{path}` stands for a file,
+/// and the command is run once per file, or `{paths}` for all of them at once. Files that no placeholder stands for go
+/// at the end.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "OpenerConfig")]
+pub struct OpenerCommand {
+    pub command: String,
+    pub mode: LaunchMode,
+}
+
+/// An [`OpenerCommand`] as written in the config: its template alone, run with [`LaunchMode::Suspend`], or along
+/// with the mode.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OpenerConfig {
+    Template(String),
+    Command {
+        command: String,
+        #[serde(default)]
+        mode: LaunchMode,
+    },
+}
+
+impl From<OpenerConfig> for OpenerCommand {
+    fn from(config: OpenerConfig) -> Self {
+        match config {
+            OpenerConfig::Template(command) => Self { command, mode: LaunchMode::default() },
+            OpenerConfig::Command { command, mode } => Self { command, mode },
+        }
+    }
+}
+
+impl OpenerCommand {
+    /// Whether the program the command runs is installed.
+    fn is_available(&self) -> bool {
+        split_words(&self.command).first().is_some_and(|program| find_program(program).is_some())
+    }
+
+    pub fn launches(&self, paths: &[PathBuf]) -> Vec<Launch> {
+        let words = split_words(&self.command);
+        let groups =
+            if words.iter().any(|word| word.contains("{path}")) { paths.chunks(1).collect() } else { vec![paths] };
+        groups
+            .into_iter()
+            .filter_map(|group| {
+                let group = group.iter().map(|path| path.to_string_lossy().into_owned()).collect::<Vec<_>>();
+                let mut args = vec![];
+                let mut placed = false;
+                for word in &words {
+                    if word == "{paths}" {
+                        args.extend(group.clone());
+                        placed = true;
+                    } else if word.contains("{path}") {
+                        args.push(word.replace("{path}", &group[0]));
+                        placed = true;
+                    } else {
+                        args.push(word.clone());
+                    }
+                }
+                if !placed {
+                    args.extend(group);
+                }
+                let (program, args) = args.split_first()?;
+                Some(Launch { program: program.clone(), args: args.to_vec(), mode: self.mode })
+            })
+            .collect()
+    }
+}
+
+/// Commands the user picked to open files, by a glob on the file name such as `*.log`, an extension such as `pdf` or
+/// a MIME type such as `image/*`. Each comes with fallbacks, tried in order until one is installed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deref, DerefMut)]
+pub struct Openers(pub HashMap<String, Vec<OpenerCommand>>);
+
+impl<'de> Deserialize<'de> for Openers {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrMany {
+            One(OpenerCommand),
+            Many(Vec<OpenerCommand>),
+        }
+
+        let parsed_map = HashMap::<String, OneOrMany>::deserialize(deserializer)?;
+        let openers = parsed_map
+            .into_iter()
+            .map(|(pattern, commands)| {
+                match commands {
+                    OneOrMany::One(command) => (pattern, vec![command]),
+                    OneOrMany::Many(commands) => (pattern, commands),
+                }
+            })
+            .collect();
+        Ok(Openers(openers))
+    }
+}
+
+impl Openers {
+    /// Commands for `path`, of type `mime`, from the most specific pattern to the least: globs, extensions, MIME
+    /// types and then MIME types ending in `/*`, each by decreasing length.
+    pub fn matching(&self, path: &Path, mime: &str) -> Vec<&OpenerCommand> {
+        let name = display_name(path);
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        let mut rules = self
+            .iter()
+            .filter_map(|(pattern, commands)| {
+                let rank = if let Some(top_level) = pattern.strip_suffix("/*") {
+                    mime.split('/').next().is_some_and(|mime| mime.eq_ignore_ascii_case(top_level)).then_some(3)
+                } else if pattern.contains('/') {
+                    pattern.eq_ignore_ascii_case(mime).then_some(2)
+                } else if pattern.contains(['*', '?', '[', '{']) {
+                    Glob::new(pattern).ok()?.compile_matcher().is_match(&name).then_some(0)
+                } else {
+                    let pattern = pattern.trim_start_matches('.').to_lowercase();
+                    (extension.as_ref() == Some(&pattern)).then_some(1)
+                }?;
+                Some(((rank, std::cmp::Reverse(pattern.len()), pattern), commands))
+            })
+            .collect::<Vec<_>>();
+        rules.sort_by_key(|(key, _)| *key);
+        rules.into_iter().flat_map(|(_, commands)| commands).collect()
+    }
+}
+
+/// An application, as registered by a `.desktop` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopEntry {
+    /// File name of the entry, e.g. `vim.desktop`, which is how `mimeapps.list` refers to it.
+    pub id: String,
+    pub name: String,
+    exec: String,
+    terminal: bool,
+    mime_types: Vec<String>,
+}
+
+impl DesktopEntry {
+    /// Reads the `[Desktop Entry]` group of a `.desktop` file. Entries that are hidden, aren't applications, or whose
+    /// program isn't installed are left out.
+    fn parse(id: &str, contents: &str) -> Option<Self> {
+        let mut fields = HashMap::new();
+        let mut in_group = false;
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_group = line == "[Desktop Entry]";
+            } else if let Some((key, value)) = line.split_once('=').filter(|_| in_group && !line.starts_with('#')) {
+                fields.insert(key.trim(), value.trim());
+            }
+        }
+        if fields.get("Type") != Some(&"Application") || fields.get("Hidden") == Some(&"true") {
+            return None;
+        }
+        if fields.get("TryExec").is_some_and(|program| find_program(program).is_none()) {
+            return None;
+        }
+        Some(Self {
+            id: id.to_owned(),
+            name: fields.get("Name").unwrap_or(&id).to_string(),
+            exec: fields.get("Exec")?.to_string(),
+            terminal: fields.get("Terminal") == Some(&"true"),
+            mime_types: fields.get("MimeType").map(|types| split_list(types)).unwrap_or_default(),
+        })
+    }
+
+    /// Programs to run to open `paths`, one per path if the application only takes one at a time.
+    pub fn launches(&self, paths: &[PathBuf]) -> Vec<Launch> {
+        let words = split_words(&self.exec);
+        let groups =
+            if words.iter().any(|word| word == "%F" || word == "%U") { vec![paths] } else { paths.chunks(1).collect() };
+        let mode = if self.terminal { LaunchMode::Suspend } else { LaunchMode::Detached };
+        groups
+            .into_iter()
+            .filter_map(|group| {
+                let group = group.iter().map(|path| path.to_string_lossy().into_owned());
+                let mut args = vec![];
+                let mut placed = false;
+                for word in &words {
+                    match word.as_str() {
+                        "%f" | "%F" | "%u" | "%U" => {
+                            args.extend(group.clone());
+                            placed = true;
+                        },
+                        // Icon, translated name and location of the entry, which we have no use for.
+                        "%i" | "%c" | "%k" => {},
+                        word => args.push(word.replace("%%", "%")),
+                    }
+                }
+                if !placed {
+                    args.extend(group);
+                }
+                let (program, args) = args.split_first()?;
+                Some(Launch { program: program.clone(), args: args.to_vec(), mode })
+            })
+            .collect()
+    }
+}
+
+/// Installed applications, which MIME types they open and which of them the user picked as defaults, per the XDG
+/// desktop entry and MIME applications specifications.
+#[derive(Debug, Default)]
+pub struct Registry {
+    /// By precedence: the ones of the user before those of the system.
+    entries: Vec<DesktopEntry>,
+    /// Ids of the entries listed for each MIME type in the sections of `mimeapps.list` files, by precedence.
+    defaults: HashMap<String, Vec<String>>,
+    added: HashMap<String, Vec<String>>,
+    removed: HashMap<String, Vec<String>>,
+    /// MIME types by lowercase file extension, from the shared MIME database, then from `mime.types` files.
+    extensions: HashMap<String, String>,
+}
+
+impl Registry {
+    pub fn load() -> Self {
+        let mut registry = Self::default();
+        let home = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+        let config_dirs = xdg_dirs("XDG_CONFIG_HOME", home.join(".config"), "XDG_CONFIG_DIRS", "/etc/xdg");
+        let data_dirs =
+            xdg_dirs("XDG_DATA_HOME", home.join(".local/share"), "XDG_DATA_DIRS", "/usr/local/share:/usr/share");
+        let application_dirs = data_dirs.iter().map(|dir| dir.join("applications")).collect::<Vec<_>>();
+
+        for dir in config_dirs.iter().chain(&application_dirs) {
+            if let Ok(contents) = fs::read_to_string(dir.join("mimeapps.list")) {
+                registry.add_mimeapps(&contents);
+            }
+        }
+        let mut seen = HashSet::new();
+        for dir in &application_dirs {
+            let Ok(read_dir) = fs::read_dir(dir) else {
+                continue;
+            };
+            let mut entries = read_dir
+                .flatten()
+                .filter(|entry| entry.path().extension().is_some_and(|extension| extension == "desktop"))
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|entry| entry.file_name());
+            for entry in entries {
+                let id = entry.file_name().to_string_lossy().into_owned();
+                // An entry of the user hides the one of the system by the same id, even if it is left out.
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                if let Some(entry) = fs::read_to_string(entry.path()).ok().and_then(|c| DesktopEntry::parse(&id, &c)) {
+                    registry.entries.push(entry);
+                }
+            }
+        }
+        for dir in &data_dirs {
+            if let Ok(contents) = fs::read_to_string(dir.join("mime/globs2")) {
+                registry.add_globs(&contents);
+            }
+        }
+        for path in [home.join(".mime.types"), PathBuf::from("/etc/mime.types")] {
+            if let Ok(contents) = fs::read_to_string(path) {
+                registry.add_mime_types(&contents);
+            }
+        }
+        registry
+    }
+
+    fn add_mimeapps(&mut self, contents: &str) {
+        let mut section = None;
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                section = match line {
+                    "[Default Applications]" => Some(&mut self.defaults),
+                    "[Added Associations]" => Some(&mut self.added),
+                    "[Removed Associations]" => Some(&mut self.removed),
+                    _ => None,
+                };
+            } else if let (Some(section), Some((mime, ids))) = (section.as_mut(), line.split_once('=')) {
+                section.entry(mime.trim().to_owned()).or_default().extend(split_list(ids));
+            }
+        }
+    }
+
+    /// Reads the `weight:type:glob` lines of a `globs2` file, heaviest first. Only the globs matching an extension are
+    /// of use here.
+    fn add_globs(&mut self, contents: &str) {
+        for line in contents.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(':').skip(1);
+            let (Some(mime), Some(glob)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Some(extension) = glob.strip_prefix("*.").filter(|extension| !extension.contains(['*', '?', '[']))
+            else {
+                continue;
+            };
+            self.extensions.entry(extension.to_lowercase()).or_insert_with(|| mime.to_owned());
+        }
+    }
+
+    fn add_mime_types(&mut self, contents: &str) {
+        for line in contents.lines().filter(|line| !line.starts_with('#')) {
+            let mut words = line.split_whitespace();
+            let Some(mime) = words.next() else {
+                continue;
+            };
+            for extension in words {
+                self.extensions.entry(extension.to_lowercase()).or_insert_with(|| mime.to_owned());
+            }
+        }
+    }
+
+    /// MIME type of `path` by its extension, or else by whether it looks like text.
+    pub fn mime_type(&self, path: &Path) -> String {
+        if path.is_dir() {
+            return "inode/directory".to_owned();
+        }
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        if let Some(mime) = extension.and_then(|extension| self.extensions.get(&extension)) {
+            return mime.clone();
+        }
+        let mut head = vec![];
+        let is_text = fs::File::open(path)
+            .and_then(|file| file.take(SNIFF_BYTES).read_to_end(&mut head))
+            .is_ok_and(|_| !head.contains(&0));
+        if is_text { "text/plain" } else { "application/octet-stream" }.to_owned()
+    }
+
+    /// Applications that open files of type `mime`, the default one first. Any text can be opened by the applications
+    /// for plain text.
+    pub fn handlers(&self, mime: &str) -> Vec<&DesktopEntry> {
+        let mut types = vec![mime];
+        if mime.starts_with("text/") && mime != "text/plain" {
+            types.push("text/plain");
+        }
+        let mut ids: Vec<&str> = vec![];
+        for mime in types {
+            let removed = self.removed.get(mime).map(Vec::as_slice).unwrap_or_default();
+            let listed = self.defaults.get(mime).into_iter().chain(self.added.get(mime)).flatten().map(String::as_str);
+            let registered = self
+                .entries
+                .iter()
+                .filter(|entry| entry.mime_types.iter().any(|registered| registered == mime))
+                .map(|entry| entry.id.as_str())
+                .filter(|id| !removed.iter().any(|removed| removed == id));
+            for id in listed.chain(registered) {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.into_iter().filter_map(|id| self.entries.iter().find(|entry| entry.id == id)).collect()
+    }
+}
+
+/// Works out which applications open which files on the blocking thread pool, and reports back the programs to run as
+/// [`Action::Launch`].
+#[derive(Default)]
+pub struct Opener {
+    action_tx: Option<UnboundedSender<Action>>,
+    openers: Openers,
+}
+
+impl Opener {
+    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
+        Self { action_tx: Some(action_tx), ..Self::default() }
+    }
+
+    pub fn set_openers(&mut self, openers: Openers) {
+        self.openers = openers;
+    }
+
+    /// Opens `paths` with the first installed command of the config for the first of them, or else with the default
+    /// application for its type, falling back to `xdg-open`, and to the editor for text.
+    pub fn open(&self, paths: Vec<PathBuf>) {
+        let Some(action_tx) = self.action_tx.clone() else {
+            return;
+        };
+        let openers = self.openers.clone();
+        tokio::task::spawn_blocking(move || {
+            let Some(first) = paths.first() else {
+                return;
+            };
+            let registry = Registry::load();
+            let mime = registry.mime_type(first);
+            let configured = openers.matching(first, &mime).into_iter().find(|command| command.is_available());
+            let action = if let Some(command) = configured {
+                Action::Launch(command.launches(&paths))
+            } else if let Some(entry) = registry.handlers(&mime).first() {
+                Action::Launch(entry.launches(&paths))
+            } else if find_program("xdg-open").is_some() {
+                let launches = paths.iter().map(|path| {
+                    Launch {
+                        program: "xdg-open".to_owned(),
+                        args: vec![path.to_string_lossy().into_owned()],
+                        mode: LaunchMode::Detached,
+                    }
+                });
+                Action::Launch(launches.collect())
+            } else if mime.starts_with("text/") {
+                Action::Edit(first.clone(), None)
+            } else {
+                Action::Error(format!("No application opens {mime} files, and xdg-open isn't installed"))
+            };
+            let _ = action_tx.send(action);
+        });
+    }
+
+    /// Asks which of the installed commands of the config and the applications registered for the type of the first
+    /// of `paths` to open them with.
+    pub fn open_with(&self, paths: Vec<PathBuf>) {
+        let Some(action_tx) = self.action_tx.clone() else {
+            return;
+        };
+        let openers = self.openers.clone();
+        tokio::task::spawn_blocking(move || {
+            let Some(first) = paths.first() else {
+                return;
+            };
+            let registry = Registry::load();
+            let mime = registry.mime_type(first);
+            let configured = openers
+                .matching(first, &mime)
+                .into_iter()
+                .filter(|command| command.is_available())
+                .map(|command| (command.command.clone(), command.launches(&paths)));
+            let registered =
+                registry.handlers(&mime).into_iter().map(|entry| (entry.name.clone(), entry.launches(&paths)));
+            let choices = configured
+                .chain(registered)
+                .take(MAX_CHOICES)
+                .zip('1'..='9')
+                .map(|((label, launches), key)| Choice::new(key, &label, Action::Launch(launches)))
+                .collect::<Vec<_>>();
+            let action = if choices.is_empty() {
+                Action::Error(format!("No application is registered for {mime} files"))
+            } else {
+                let name = match paths.as_slice() {
+                    [path] => display_name(path),
+                    paths => format!("{} items", paths.len()),
+                };
+                Action::Choose(format!("Open {name} ({mime}) with"), choices)
+            };
+            let _ = action_tx.send(action);
+        });
+    }
+}
+
+/// The directory named by `home_var`, defaulting to `home`, then the ones listed in `dirs_var`, defaulting to `dirs`.
+fn xdg_dirs(home_var: &str, home: PathBuf, dirs_var: &str, dirs: &str) -> Vec<PathBuf> {
+    let home = env::var_os(home_var).filter(|var| !var.is_empty()).map(PathBuf::from).unwrap_or(home);
+    let dirs = env::var_os(dirs_var).filter(|var| !var.is_empty()).unwrap_or_else(|| dirs.into());
+    std::iter::once(home).chain(env::split_paths(&dirs)).collect()
+}
+
+/// Splits a `;` separated list of a desktop entry or `mimeapps.list`.
+fn split_list(list: &str) -> Vec<String> {
+    list.split(';').map(str::trim).filter(|item| !item.is_empty()).map(str::to_owned).collect()
+}
+
+/// Splits a command line, like the `Exec` key of a desktop entry or an opener template, into words, undoing its
+/// quotes and the backslash escapes in double quotes.
+fn split_words(command: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' if quote.is_none() => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            },
+            c if quote == Some(c) => quote = None,
+            '\\' if quote == Some('"') => word.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() && quote.is_none() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Where `program` is, if it is a path to it or is found in `$PATH`.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        return Some(PathBuf::from(program)).filter(|path| path.is_file());
+    }
+    env::split_paths(&env::var_os("PATH")?).map(|dir| dir.join(program)).find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let entry = |id: &str, exec: &str, terminal: bool, mime_types: &str| {
+            let contents = format!(
+                "[Desktop Entry]\nType=Application\nName={id}\nExec={exec}\nTerminal={terminal}\nMimeType={mime_types}\n\
+                 [Desktop Action new-window]\nExec=ignored\n"
+            );
+            DesktopEntry::parse(&format!("{id}.desktop"), &contents).unwrap()
+        };
+        let mut registry = Registry {
+            entries: vec![
+                entry("viewer", "\"/opt/my viewer/bin\" --open %U", false, "image/png;text/plain;"),
+                entry("editor", "ed %f", true, "text/plain;"),
+            ],
+            ..Registry::default()
+        };
+        registry.add_mimeapps(
+            "[Default Applications]\ntext/plain=editor.desktop\n[Removed Associations]\nimage/png=viewer.desktop;\n",
+        );
+        let ids = |mime| registry.handlers(mime).iter().map(|entry| entry.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids("text/plain"), vec!["editor.desktop", "viewer.desktop"]);
+        assert_eq!(ids("image/png"), Vec::<String>::new());
+        assert_eq!(ids("text/x-rust"), vec!["editor.desktop", "viewer.desktop"]);
+
+        let paths = [PathBuf::from("a.txt"), PathBuf::from("b.txt")];
+        assert_eq!(registry.entries[0].launches(&paths), vec![Launch {
+            program: "/opt/my viewer/bin".to_owned(),
+            args: vec!["--open".to_owned(), "a.txt".to_owned(), "b.txt".to_owned()],
+            mode: LaunchMode::Detached,
+        }]);
+        let launches = registry.entries[1].launches(&paths);
+        assert_eq!(launches.iter().map(|launch| launch.args.clone()).collect::<Vec<_>>(), vec![vec!["a.txt"], vec![
+            "b.txt"
+        ]]);
+        assert_eq!(launches[0].mode, LaunchMode::Suspend);
+    }
+
+    #[test]
+    fn test_openers() {
+        let openers: Openers = json5::from_str(
+            r#"{
+                "*.log": "less +G {path}",
+                "log": "tail",
+                "text/*": [{ "command": "'my editor' --files={path}", "mode": "Block" }, "cat {paths}"],
+                "text/plain": { "command": "bat {paths} --paging always", "mode": "Detached" },
+            }"#,
+        )
+        .unwrap();
+        let commands = |path: &str, mime| {
+            openers.matching(Path::new(path), mime).iter().map(|command| command.command.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(commands("/var/log/Syslog.LOG", "text/plain"), vec![
+            "tail",
+            "bat {paths} --paging always",
+            "'my editor' --files={path}",
+            "cat {paths}"
+        ]);
+        assert_eq!(commands("boot.log", "text/x-log"), vec![
+            "less +G {path}",
+            "tail",
+            "'my editor' --files={path}",
+            "cat {paths}"
+        ]);
+        assert_eq!(commands("a.png", "image/png"), Vec::<String>::new());
+
+        let paths = [PathBuf::from("a b.txt"), PathBuf::from("c.txt")];
+        let launches = openers["text/*"][0].launches(&paths);
+        assert_eq!(launches.iter().map(|launch| launch.program.as_str()).collect::<Vec<_>>(), vec![
+            "my editor",
+            "my editor"
+        ]);
+        assert_eq!(launches[0].args, vec!["--files=a b.txt"]);
+        assert_eq!(launches[0].mode, LaunchMode::Block);
+        assert_eq!(openers["text/plain"][0].launches(&paths)[0].args, vec!["a b.txt", "c.txt", "--paging", "always"]);
+        assert_eq!(openers["log"][0].launches(&paths)[0].args, vec!["a b.txt", "c.txt"]);
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use lazy_static::lazy_static;
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use super::image::{self, ImageProtocol, RenderedImage};
+use crate::action::Action;
+
+/// Only the start of bigger files is previewed.
+const MAX_BYTES: u64 = 256 * 1024;
+const MAX_LINES: usize = 2000;
+/// A file is taken for binary if there is a NUL byte this close to its start.
+const SNIFF_BYTES: usize = 8 * 1024;
+const MAX_DIR_ENTRIES: usize = 1000;
+/// Binary files are read this much at a time for the hex view.
+pub const HEX_PAGE_SIZE: usize = 64 * 1024;
+const TAB_WIDTH: usize = 4;
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME: Theme = ThemeSet::load_defaults().themes.remove("base16-ocean.dark").unwrap_or_default();
+}
+
+/// What the preview of an entry shows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PreviewContent {
+    /// Lines of highlighted text, cut short if the file is too big.
+    Text {
+        lines: Vec<Vec<(String, Style)>>,
+        truncated: bool,
+    },
+    /// Length of a binary file, and its first [`HEX_PAGE_SIZE`] bytes.
+    Binary {
+        len: u64,
+        head: Vec<u8>,
+    },
+    /// Size of an image in pixels. The image itself is rendered to fit the pane with [`Previewer::load_image`].
+    Image {
+        width: u32,
+        height: u32,
+    },
+    /// Names of the entries of a directory, and whether each is a directory itself.
+    Directory(Vec<(String, bool)>),
+    Error(String),
+}
+
+/// Preview of `path` as it was when last modified at `modified`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preview {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub content: PreviewContent,
+}
+
+/// Bytes of `path` read from `offset` on, to page through a binary file without reading it whole.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HexPage {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Image at `path`, as it was when last modified at `modified`, rendered to fit in `width` by `height` cells.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImagePreview {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub width: u16,
+    pub height: u16,
+    pub image: RenderedImage,
+}
+
+/// Loads previews on the blocking thread pool. Like [`Lister`](super::listing::Lister), loading a new preview
+/// cancels the one in flight, which matters when scrolling quickly through big files.
+#[derive(Default)]
+pub struct Previewer {
+    action_tx: Option<UnboundedSender<Action>>,
+    in_flight: Option<CancellationToken>,
+    /// The hex page or image being loaded, apart from the preview so that neither cancels the other.
+    page_in_flight: Option<CancellationToken>,
+}
+
+impl Previewer {
+    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
+        // Loading the syntax definitions takes a while, better not make the first preview wait for it.
+        tokio::task::spawn_blocking(|| {
+            lazy_static::initialize(&SYNTAX_SET);
+            lazy_static::initialize(&THEME);
+        });
+        Self { action_tx: Some(action_tx), ..Self::default() }
+    }
+
+    /// Starts loading the preview of `path`, which comes back as [`Action::PreviewLoaded`]. When there is a preview
+    /// of it already, made when the file was last modified at `cached`, nothing comes back unless it was modified
+    /// since.
+    pub fn load(&mut self, path: &Path, cached: Option<Option<SystemTime>>) {
+        cancel(&mut self.in_flight);
+        let Some(action_tx) = self.action_tx.clone() else {
+            return;
+        };
+        let token = CancellationToken::new();
+        self.in_flight = Some(token.clone());
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            // Checked here rather than by the caller, as that can take a while on a network mount.
+            let modified = path.metadata().and_then(|metadata| metadata.modified()).ok();
+            if cached == Some(modified) {
+                return;
+            }
+            let content = load(&path, &token).unwrap_or_else(|e| PreviewContent::Error(e.to_string()));
+            if !token.is_cancelled() {
+                let _ = action_tx.send(Action::PreviewLoaded(Preview { path, modified, content }));
+            }
+        });
+    }
+
+    /// Starts reading a [`HEX_PAGE_SIZE`] page of `path` at `offset`, which comes back as [`Action::HexLoaded`].
+    pub fn load_hex_page(&mut self, path: &Path, offset: u64) {
+        cancel(&mut self.page_in_flight);
+        let Some(action_tx) = self.action_tx.clone() else {
+            return;
+        };
+        let token = CancellationToken::new();
+        self.page_in_flight = Some(token.clone());
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            match read_at(&path, offset, HEX_PAGE_SIZE) {
+                Ok(bytes) if !token.is_cancelled() => {
+                    let _ = action_tx.send(Action::HexLoaded(HexPage { path, offset, bytes }));
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    let _ = action_tx.send(Action::Error(format!("Failed to read {}: {e}", path.display())));
+                },
+            }
+        });
+    }
+
+    /// Starts decoding the image at `path`, last modified at `modified`, and scaling it down to `width` by `height`
+    /// cells, which comes back as [`Action::ImageLoaded`].
+    pub fn load_image(
+        &mut self,
+        path: &Path,
+        modified: Option<SystemTime>,
+        width: u16,
+        height: u16,
+        protocol: ImageProtocol,
+    ) {
+        cancel(&mut self.page_in_flight);
+        let Some(action_tx) = self.action_tx.clone() else {
+            return;
+        };
+        let token = CancellationToken::new();
+        self.page_in_flight = Some(token.clone());
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let image = image::render(&path, width, height, protocol);
+            if !token.is_cancelled() {
+                let _ = action_tx.send(Action::ImageLoaded(ImagePreview { path, modified, width, height, image }));
+            }
+        });
+    }
+
+    pub fn cancel(&mut self) {
+        cancel(&mut self.in_flight);
+        cancel(&mut self.page_in_flight);
+    }
+}
+
+fn cancel(in_flight: &mut Option<CancellationToken>) {
+    if let Some(token) = in_flight.take() {
+        token.cancel();
+    }
+}
+
+fn load(path: &Path, token: &CancellationToken) -> io::Result<PreviewContent> {
+    let metadata = path.metadata()?;
+    if metadata.is_dir() {
+        return list_dir(path);
+    }
+    if image::is_image(path) {
+        // Files that only look like images by their name get the usual preview.
+        if let Ok((width, height)) = ::image::image_dimensions(path) {
+            return Ok(PreviewContent::Image { width, height });
+        }
+    }
+    let mut contents = vec![];
+    fs::File::open(path)?.take(MAX_BYTES).read_to_end(&mut contents)?;
+    let binary = |mut contents: Vec<u8>| {
+        contents.truncate(HEX_PAGE_SIZE);
+        Ok(PreviewContent::Binary { len: metadata.len(), head: contents })
+    };
+    if contents[..contents.len().min(SNIFF_BYTES)].contains(&0) {
+        return binary(contents);
+    }
+    let text = match std::str::from_utf8(&contents) {
+        Ok(text) => text,
+        // The read may have stopped in the middle of a character.
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&contents[..e.valid_up_to()]).unwrap(),
+        Err(_) => return binary(contents),
+    };
+    let first_line = text.lines().next().unwrap_or_default();
+    let syntax = SYNTAX_SET
+        .find_syntax_for_file(path)
+        .ok()
+        .flatten()
+        .or_else(|| SYNTAX_SET.find_syntax_by_first_line(first_line))
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, &THEME);
+    let mut lines = vec![];
+    for line in LinesWithEndings::from(text).take(MAX_LINES) {
+        if token.is_cancelled() {
+            break;
+        }
+        let spans = highlighter.highlight_line(line, &SYNTAX_SET).unwrap_or_else(|_| vec![(Default::default(), line)]);
+        lines.push(
+            spans
+                .into_iter()
+                .map(|(style, text)| {
+                    (text.trim_end_matches(['\n', '\r']).replace('\t', &" ".repeat(TAB_WIDTH)), convert(style))
+                })
+                .filter(|(text, _)| !text.is_empty())
+                .collect(),
+        );
+    }
+    let truncated = metadata.len() > MAX_BYTES || (lines.len() == MAX_LINES && text.lines().nth(MAX_LINES).is_some());
+    Ok(PreviewContent::Text { lines, truncated })
+}
+
+fn read_at(path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::with_capacity(len);
+    file.take(len as u64).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn convert(style: syntect::highlighting::Style) -> Style {
+    let mut converted = Style::new().fg(Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        converted = converted.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        converted = converted.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        converted = converted.add_modifier(Modifier::UNDERLINED);
+    }
+    converted
+}
+
+/// Directories first, then by name ignoring case.
+fn list_dir(path: &Path) -> io::Result<PreviewContent> {
+    let mut entries = fs::read_dir(path)?
+        .flatten()
+        .take(MAX_DIR_ENTRIES)
+        .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path().is_dir()))
+        .collect::<Vec<_>>();
+    entries.sort_by_cached_key(|(name, is_dir)| (!is_dir, name.to_lowercase()));
+    Ok(PreviewContent::Directory(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_skips_fresh_preview() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("notes.txt");
+        fs::write(&path, "hello\n").unwrap();
+        let modified = path.metadata().unwrap().modified().ok();
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut previewer = Previewer { action_tx: Some(action_tx), ..Previewer::default() };
+
+        previewer.load(&path, Some(None));
+        let Some(Action::PreviewLoaded(preview)) = action_rx.recv().await else {
+            panic!("stale preview not reloaded");
+        };
+        assert_eq!(preview.modified, modified);
+
+        previewer.load(&path, Some(modified));
+        // The task drops its sender without sending, so the channel closes once the previewer is gone too.
+        drop(previewer);
+        assert_eq!(action_rx.recv().await, None);
+    }
+
+    #[test]
+    fn test_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("main.rs"), "fn main() {\n\tlet x = 1;\n}\n").unwrap();
+        fs::write(dir.join("data.bin"), [0x7f, b'E', b'L', b'F', 0, 1]).unwrap();
+        let token = CancellationToken::new();
+
+        let PreviewContent::Text { lines, truncated } = load(&dir.join("main.rs"), &token).unwrap() else {
+            panic!("expected text");
+        };
+        assert!(!truncated);
+        let text = lines.iter().map(|spans| spans.iter().map(|(text, _)| text.as_str()).collect::<String>());
+        assert_eq!(text.collect::<Vec<_>>(), vec!["fn main() {", "    let x = 1;", "}"]);
+        // Keywords aren't the same color as identifiers.
+        assert!(lines[0].len() > 1);
+
+        assert_eq!(load(&dir.join("data.bin"), &token).unwrap(), PreviewContent::Binary {
+            len: 6,
+            head: vec![0x7f, b'E', b'L', b'F', 0, 1]
+        });
+        assert_eq!(read_at(&dir.join("data.bin"), 4, 10).unwrap(), vec![0, 1]);
+        assert_eq!(
+            load(dir, &token).unwrap(),
+            PreviewContent::Directory(vec![("data.bin".to_owned(), false), ("main.rs".to_owned(), false)])
+        );
+    }
+}
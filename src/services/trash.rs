@@ -0,0 +1,278 @@
+//! Trash following the [freedesktop.org trash specification](https://specifications.freedesktop.org/trash-spec/):
+//! trashed files go to `files/` in a trash directory on the same filesystem, along with a `.trashinfo` file in
+//! `info/` recording where they came from and when.
+//!
+//! Files on the filesystem of the home directory go to the home trash, `$XDG_DATA_HOME/Trash`. Files on other
+//! filesystems go to `$top/.Trash/$uid` if the administrator set up `$top/.Trash`, or else to `$top/.Trash-$uid`,
+//! `$top` being where the filesystem is mounted.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use super::fileops::{self, display_name};
+
+const INFO_EXTENSION: &str = "trashinfo";
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// An entry of a trash directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashedItem {
+    /// Trash directory the item is in, the one containing `files/` and `info/`.
+    pub trash_dir: PathBuf,
+    /// Name of the item in `files/`, which can differ from the original one when several items with the same name
+    /// were trashed.
+    pub name: String,
+    pub original_path: PathBuf,
+    pub deleted: Option<NaiveDateTime>,
+    pub is_dir: bool,
+}
+
+impl TrashedItem {
+    pub fn files_path(&self) -> PathBuf {
+        self.trash_dir.join("files").join(&self.name)
+    }
+
+    pub fn info_path(&self) -> PathBuf {
+        self.trash_dir.join("info").join(format!("{}.{INFO_EXTENSION}", self.name))
+    }
+
+    /// Moves the item back to where it was trashed from, recreating missing parent directories. Refuses to overwrite
+    /// anything that took its place since.
+    pub fn restore(&self) -> io::Result<()> {
+        fileops::ensure_absent(&self.original_path)?;
+        if let Some(parent) = self.original_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.files_path(), &self.original_path)?;
+        fs::remove_file(self.info_path())
+    }
+}
+
+pub fn home_trash() -> Option<PathBuf> {
+    Some(directories::BaseDirs::new()?.data_dir().join("Trash"))
+}
+
+/// Moves `path` to the trash directory of its filesystem, and returns the resulting item.
+pub fn trash(path: &Path) -> io::Result<TrashedItem> {
+    let path = std::path::absolute(path)?;
+    let trash_dir = trash_dir_for(&path)?;
+    let (files, info) = (trash_dir.join("files"), trash_dir.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+    let deleted = Local::now().naive_local();
+    let contents = format!("[Trash Info]\nPath={}\nDeletionDate={}\n", encode(&path), deleted.format(DATE_FORMAT));
+
+    // Creating the info file first reserves the name, as the specification requires.
+    let original_name = display_name(&path);
+    let mut n = 1;
+    let name = loop {
+        let name = if n == 1 { original_name.clone() } else { format!("{original_name}.{n}") };
+        let info_path = info.join(format!("{name}.{INFO_EXTENSION}"));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(_) if files.join(&name).symlink_metadata().is_ok() => {
+                let _ = fs::remove_file(&info_path);
+            },
+            Ok(_) => {
+                fs::write(&info_path, &contents)?;
+                break name;
+            },
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+            Err(e) => return Err(e),
+        }
+        n += 1;
+    };
+
+    let item = TrashedItem { trash_dir, name, is_dir: path.is_dir(), original_path: path, deleted: Some(deleted) };
+    if let Err(e) = fs::rename(&item.original_path, item.files_path()) {
+        let _ = fs::remove_file(item.info_path());
+        return Err(e);
+    }
+    Ok(item)
+}
+
+/// Lists the items of every trash directory that can be found: the home trash and those at the top of the mounted
+/// filesystems. Most recently trashed first.
+pub fn list() -> Vec<TrashedItem> {
+    let mut items = vec![];
+    for trash_dir in trash_dirs() {
+        let Ok(entries) = fs::read_dir(trash_dir.join("info")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == INFO_EXTENSION) {
+                if let Some(item) = read_info(&trash_dir, &path) {
+                    items.push(item);
+                }
+            }
+        }
+    }
+    items.sort_by_key(|item| std::cmp::Reverse(item.deleted));
+    items
+}
+
+fn read_info(trash_dir: &Path, info_path: &Path) -> Option<TrashedItem> {
+    let name = info_path.file_stem()?.to_string_lossy().into_owned();
+    let contents = fs::read_to_string(info_path).ok()?;
+    let mut original_path = None;
+    let mut deleted = None;
+    for line in contents.lines() {
+        if let Some(value) = line.strip_prefix("Path=") {
+            original_path = Some(decode(value));
+        } else if let Some(value) = line.strip_prefix("DeletionDate=") {
+            deleted = NaiveDateTime::parse_from_str(value.trim(), DATE_FORMAT).ok();
+        }
+    }
+    // Relative paths are relative to the top of the filesystem the trash directory is on.
+    let original_path = match original_path? {
+        path if path.is_absolute() => path,
+        path => top_of_trash_dir(trash_dir)?.join(path),
+    };
+    let files_path = trash_dir.join("files").join(&name);
+    let is_dir = files_path.symlink_metadata().ok()?.is_dir();
+    Some(TrashedItem { trash_dir: trash_dir.to_path_buf(), name, original_path, deleted, is_dir })
+}
+
+/// `$top` of a `$top/.Trash/$uid` or `$top/.Trash-$uid` directory.
+fn top_of_trash_dir(trash_dir: &Path) -> Option<&Path> {
+    let parent = trash_dir.parent()?;
+    if parent.file_name().is_some_and(|name| name == ".Trash") {
+        parent.parent()
+    } else {
+        Some(parent)
+    }
+}
+
+#[cfg(unix)]
+fn trash_dir_for(path: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let not_found = || io::Error::new(io::ErrorKind::NotFound, "no trash directory available");
+    let device = path.symlink_metadata()?.dev();
+    let home_trash = home_trash().ok_or_else(not_found)?;
+    // The home trash may not exist yet, so compare with the closest existing ancestor.
+    let home_device = home_trash.ancestors().find_map(|dir| dir.metadata().ok()).map(|metadata| metadata.dev());
+    if home_device == Some(device) {
+        return Ok(home_trash);
+    }
+
+    let top = mount_point(path)?;
+    let uid = unsafe { libc::getuid() };
+    // `$top/.Trash` is only used if it is a real directory with the sticky bit set, to keep users from messing with
+    // each other's files.
+    let admin_trash = top.join(".Trash");
+    if let Ok(metadata) = admin_trash.symlink_metadata() {
+        if metadata.is_dir() && metadata.permissions().mode() & 0o1000 != 0 {
+            let user_trash = admin_trash.join(uid.to_string());
+            if fs::create_dir_all(&user_trash).is_ok() {
+                return Ok(user_trash);
+            }
+        }
+    }
+    let user_trash = top.join(format!(".Trash-{uid}"));
+    fs::create_dir_all(&user_trash)?;
+    fs::set_permissions(&user_trash, fs::Permissions::from_mode(0o700))?;
+    Ok(user_trash)
+}
+
+#[cfg(not(unix))]
+fn trash_dir_for(path: &Path) -> io::Result<PathBuf> {
+    home_trash().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no trash directory available"))
+}
+
+/// Topmost ancestor of `path` on the same filesystem.
+#[cfg(unix)]
+fn mount_point(path: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = path.symlink_metadata()?.dev();
+    let mut top = path.parent().unwrap_or(path);
+    while let Some(parent) = top.parent() {
+        if parent.metadata()?.dev() != device {
+            break;
+        }
+        top = parent;
+    }
+    Ok(top.to_path_buf())
+}
+
+/// The home trash and the trash directories of the current user at the top of every mounted filesystem.
+fn trash_dirs() -> Vec<PathBuf> {
+    let mut dirs = home_trash().into_iter().collect::<Vec<_>>();
+    #[cfg(unix)]
+    {
+        let uid = unsafe { libc::getuid() };
+        let mounts = fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+        for mount_point in mounts.lines().filter_map(|line| line.split_whitespace().nth(1)) {
+            // Spaces and the like are escaped in octal in the mount table.
+            let top = PathBuf::from(mount_point.replace("\\040", " ").replace("\\011", "\t"));
+            for dir in [top.join(".Trash").join(uid.to_string()), top.join(format!(".Trash-{uid}"))] {
+                if dir.is_dir() && !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+    }
+    dirs
+}
+
+/// Escapes a path for the `Path` key of a `.trashinfo` file, the way URLs are escaped.
+fn encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn decode(value: &str) -> PathBuf {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped =
+            bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+    #[cfg(unix)]
+    return PathBuf::from(<std::ffi::OsString as std::os::unix::ffi::OsStringExt>::from_vec(decoded));
+    #[cfg(not(unix))]
+    return PathBuf::from(String::from_utf8_lossy(&decoded).into_owned());
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let path = Path::new("/home/me/My Files/100%/été.txt");
+        assert_eq!(encode(path), "/home/me/My%20Files/100%25/%C3%A9t%C3%A9.txt");
+        assert_eq!(decode(&encode(path)), path);
+    }
+
+    #[test]
+    fn test_top_of_trash_dir() {
+        assert_eq!(top_of_trash_dir(Path::new("/mnt/usb/.Trash/1000")), Some(Path::new("/mnt/usb")));
+        assert_eq!(top_of_trash_dir(Path::new("/mnt/usb/.Trash-1000")), Some(Path::new("/mnt/usb")));
+    }
+}
@@ -0,0 +1,199 @@
+use std::{cmp::Ordering, fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::services::listing::DirEntry;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Extension,
+    Type,
+}
+
+impl SortKey {
+    /// The key that comes after this one when cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Name => SortKey::Size,
+            SortKey::Size => SortKey::Modified,
+            SortKey::Modified => SortKey::Extension,
+            SortKey::Extension => SortKey::Type,
+            SortKey::Type => SortKey::Name,
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Extension => "extension",
+            SortKey::Type => "type",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the entries of a directory are ordered.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SortSpec {
+    pub key: SortKey,
+    pub descending: bool,
+    /// Keep directories above files regardless of the key and direction.
+    pub dirs_first: bool,
+    pub case_insensitive: bool,
+}
+
+impl Default for SortSpec {
+    fn default() -> Self {
+        Self { key: SortKey::Name, descending: false, dirs_first: true, case_insensitive: true }
+    }
+}
+
+impl fmt::Display for SortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.key, if self.descending { '▼' } else { '▲' })?;
+        if self.dirs_first {
+            write!(f, ", dirs first")?;
+        }
+        if !self.case_insensitive {
+            write!(f, ", case sensitive")?;
+        }
+        Ok(())
+    }
+}
+
+impl SortSpec {
+    pub fn sort(&self, entries: &mut [DirEntry]) {
+        // A stable sort is cheap on the mostly sorted slices we get while a listing streams in.
+        entries.sort_by(|a, b| self.compare(a, b));
+    }
+
+    pub fn compare(&self, a: &DirEntry, b: &DirEntry) -> Ordering {
+        if self.dirs_first && a.is_dir != b.is_dir {
+            return b.is_dir.cmp(&a.is_dir);
+        }
+        let by_name = || natural_cmp(&a.name, &b.name, self.case_insensitive);
+        let ordering = match self.key {
+            SortKey::Name => by_name(),
+            SortKey::Size => a.size.cmp(&b.size).then_with(by_name),
+            SortKey::Modified => a.modified.cmp(&b.modified).then_with(by_name),
+            SortKey::Extension => {
+                natural_cmp(extension(&a.name), extension(&b.name), self.case_insensitive).then_with(by_name)
+            },
+            SortKey::Type => a.kind.cmp(&b.kind).then_with(by_name),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+fn extension(name: &str) -> &str {
+    Path::new(name).extension().and_then(|extension| extension.to_str()).unwrap_or_default()
+}
+
+/// Compares strings the way people expect version numbers and numbered files to be ordered, i.e. `file2` before
+/// `file10`. Runs of digits are compared by their numeric value, everything else character by character.
+pub fn natural_cmp(a: &str, b: &str, case_insensitive: bool) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_digits = take_digits(&mut a_chars);
+                let y_digits = take_digits(&mut b_chars);
+                let x_trimmed = x_digits.trim_start_matches('0');
+                let y_trimmed = y_digits.trim_start_matches('0');
+                let ordering = x_trimmed.len().cmp(&y_trimmed.len()).then_with(|| x_trimmed.cmp(y_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            },
+            (Some(x), Some(y)) => {
+                let ordering = if case_insensitive { x.to_lowercase().cmp(y.to_lowercase()) } else { x.cmp(&y) };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            },
+        }
+    }
+    // Equal apart from case or leading zeros: fall back to a plain comparison so the order is still total.
+    a.cmp(b)
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn names(entries: &[DirEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    fn entry(name: &str, is_dir: bool, size: Option<u64>) -> DirEntry {
+        DirEntry { name: name.to_owned(), is_dir, size, ..DirEntry::default() }
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("file2", "file10", false), Ordering::Less);
+        assert_eq!(natural_cmp("v1.10.0", "v1.9.3", false), Ordering::Greater);
+        assert_eq!(natural_cmp("a", "B", true), Ordering::Less);
+        assert_eq!(natural_cmp("a", "B", false), Ordering::Greater);
+        assert_eq!(natural_cmp("img007", "img7", false), Ordering::Less);
+        assert_eq!(natural_cmp("abc", "abc", true), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_sort_dirs_first() {
+        let mut entries = vec![
+            entry("b.txt", false, Some(1)),
+            entry("z", true, None),
+            entry("a10", false, Some(3)),
+            entry("a9", true, None),
+        ];
+        SortSpec::default().sort(&mut entries);
+        assert_eq!(names(&entries), vec!["a9", "z", "a10", "b.txt"]);
+
+        SortSpec { descending: true, ..SortSpec::default() }.sort(&mut entries);
+        assert_eq!(names(&entries), vec!["z", "a9", "b.txt", "a10"]);
+    }
+
+    #[test]
+    fn test_sort_by_size() {
+        let mut entries =
+            vec![entry("big", false, Some(100)), entry("small", false, Some(1)), entry("dir", true, None)];
+        SortSpec { key: SortKey::Size, dirs_first: false, ..SortSpec::default() }.sort(&mut entries);
+        assert_eq!(names(&entries), vec!["dir", "small", "big"]);
+    }
+
+    #[test]
+    fn test_sort_by_extension() {
+        let mut entries = vec![entry("b.rs", false, None), entry("a.toml", false, None), entry("c.md", false, None)];
+        SortSpec { key: SortKey::Extension, ..SortSpec::default() }.sort(&mut entries);
+        assert_eq!(names(&entries), vec!["c.md", "b.rs", "a.toml"]);
+    }
+}
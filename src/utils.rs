@@ -1,4 +1,10 @@
-use std::path::PathBuf;
+use std::{
+    collections::hash_map::RandomState,
+    fs,
+    hash::{BuildHasher, Hasher},
+    io,
+    path::PathBuf,
+};
 
 use color_eyre::eyre::Result;
 use directories::ProjectDirs;
@@ -83,6 +89,25 @@ pub fn get_data_dir() -> PathBuf {
     directory
 }
 
+/// Creates a file in the temporary directory that only the user can read and write, under a name nobody can guess
+/// ahead of time, e.g. `<package>-<purpose>-<random><extension>`. A file already there, like a symlink planted by
+/// someone else, is never opened.
+pub fn create_temp_file(purpose: &str, extension: &str) -> io::Result<(PathBuf, fs::File)> {
+    loop {
+        // Each `RandomState` is seeded differently, which is random enough without pulling in a crate for it.
+        let random = RandomState::new().build_hasher().finish();
+        let path = std::env::temp_dir().join(format!("{}-{purpose}-{random:016x}{extension}", env!("CARGO_PKG_NAME")));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+            result => return result.map(|file| (path, file)),
+        }
+    }
+}
+
 pub fn get_config_dir() -> PathBuf {
     let directory = if let Some(s) = CONFIG_FOLDER.clone() {
         s